@@ -0,0 +1,45 @@
+use std::hint::black_box;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+
+use terraria_cool_colorer::{Colorer, ColorerSettings, Color};
+
+
+fn color_text(c: &mut Criterion)
+{
+    let colors = vec![Color::new(255, 0, 0), Color::new(0, 255, 0), Color::new(0, 0, 255)];
+
+    let settings = ColorerSettings{seed: Some(0), ..Default::default()};
+    let mut colorer = Colorer::new(colors, settings);
+
+    let mut group = c.benchmark_group("color_text");
+
+    for (name, message) in [
+        ("short", "hello everyone".to_string()),
+        ("long", "the quick brown fox jumps over the lazy dog ".repeat(40)),
+        ("tags", "look at my [i:29] and [c/ff0000:this] [brackets] ".repeat(20))
+    ]
+    {
+        group.throughput(Throughput::Bytes(message.len() as u64));
+        group.bench_function(name, |b|
+        {
+            b.iter(|| colorer.color_text(black_box(&message)))
+        });
+    }
+
+    //the single color is worked out once per message instead of for every character
+    let mut solid = Colorer::new(vec![Color::new(255, 0, 255)], ColorerSettings::default());
+
+    let message = "the quick brown fox jumps over the lazy dog ".repeat(40);
+
+    group.throughput(Throughput::Bytes(message.len() as u64));
+    group.bench_function("solid", |b|
+    {
+        b.iter(|| solid.color_text(black_box(&message)))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, color_text);
+criterion_main!(benches);
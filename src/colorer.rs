@@ -1,302 +1,2304 @@
 use std::fmt;
 
+use std::borrow::Cow;
 
-#[derive(Clone)]
+use std::str::FromStr;
+
+use std::time::Instant;
+
+use std::sync::Arc;
+
+use rand::{Rng, RngCore, SeedableRng, rngs::StdRng};
+
+
+//the [c/rrggbb:] around every run of text
+const TAG_LENGTH: usize = 11;
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Interpolation
 {
     Random,
     Nearest,
     Linear,
-    Cubic
+    Smoothstep,
+    Bias(f32),
+    LinearGamma(f32),
+    Cubic,
+    Hsv,
+    Oklch
 }
 
-#[derive(Clone)]
-pub struct Color
+impl Interpolation
 {
-    r: u8,
-    g: u8,
-    b: u8
+    pub const DEFAULT_GAMMA: f32 = 2.2;
 }
 
-impl Color
+impl TryFrom<&str> for Interpolation
 {
-    pub fn new(r: u8, g: u8, b: u8) -> Self
+    type Error = String;
+
+    fn try_from(name: &str) -> Result<Self, Self::Error>
+    {
+        //bias takes its amount after a colon like bias:0.3
+        if let Some(bias) = name.to_lowercase().strip_prefix("bias:")
+        {
+            let bias: f32 = bias.parse()
+                .map_err(|err| format!("{err} cannot be converted to bias amount"))?;
+
+            if !(bias>0.0 && bias<1.0)
+            {
+                return Err(format!("bias amount must be between 0 and 1, got {bias}"));
+            }
+
+            return Ok(Interpolation::Bias(bias));
+        }
+
+        match name.to_lowercase().as_str()
+        {
+            "random" => Ok(Interpolation::Random),
+            "nearest" => Ok(Interpolation::Nearest),
+            "linear" => Ok(Interpolation::Linear),
+            "smoothstep" => Ok(Interpolation::Smoothstep),
+            "bias" => Ok(Interpolation::Bias(0.5)),
+            "linear-gamma" => Ok(Interpolation::LinearGamma(Interpolation::DEFAULT_GAMMA)),
+            "cubic" => Ok(Interpolation::Cubic),
+            "hsv" => Ok(Interpolation::Hsv),
+            "oklch" => Ok(Interpolation::Oklch),
+            _ => Err(format!("{name} is not a valid interpolation"))
+        }
+    }
+}
+
+//anything that can blend two colors, custom curves only need blend for a single channel
+//while ones that need the whole color or the stops around it override blend_colors
+pub trait Interpolator: Send + Sync
+{
+    fn blend(&self, lhs: u8, rhs: u8, amount: f32) -> u8;
+
+    //before and after are the stops around lhs and rhs, rng is the colorers own
+    //so seeded colorers stay reproducible
+    fn blend_colors(
+        &self,
+        before: &Color,
+        lhs: &Color,
+        rhs: &Color,
+        after: &Color,
+        amount: f32,
+        rng: &mut dyn RngCore
+        ) -> Color
     {
-        Color{r, g, b}
+        let _ = (before, after, rng);
+
+        lhs.interpolate_inner(*rhs, |lhs, rhs| self.blend(lhs, rhs, amount))
     }
+}
 
-    pub fn interpolate(&self, other: &Color, amount: f32, interpolation: &Interpolation) -> Color
+impl Interpolator for Interpolation
+{
+    fn blend(&self, lhs: u8, rhs: u8, amount: f32) -> u8
     {
-        match interpolation
+        let linear = |amount: f32|
+        {
+            let diff = rhs as i32 - lhs as i32;
+            let result = lhs as f32 + diff as f32*amount;
+
+            result.round() as u8
+        };
+
+        match self
         {
             Interpolation::Random =>
             {
-                self.interpolate_inner(other, |lhs, rhs|
+                if rand::thread_rng().gen::<f32>()<0.5
+                {
+                    lhs
+                } else
                 {
-                    if rand::random::<f32>()<0.5
-                    {
-                        lhs
-                    } else
-                    {
-                        rhs
-                    }
-                })
+                    rhs
+                }
             },
             Interpolation::Nearest =>
             {
-                self.interpolate_inner(other, |lhs, rhs|
+                if amount<0.5
                 {
-                    if amount<0.5
-                    {
-                        lhs
-                    } else
-                    {
-                        rhs
-                    }
-                })
+                    lhs
+                } else
+                {
+                    rhs
+                }
             },
-            Interpolation::Linear =>
+            Interpolation::Smoothstep =>
             {
-                self.interpolate_inner(other, |lhs, rhs|
-                {
-                    let diff = rhs as i32 - lhs as i32;
-                    let result = lhs as f32 + diff as f32*amount;
+                //eases in and out of each stop instead of a straight ramp
+                let t = amount.clamp(0.0, 1.0);
 
-                    result.round() as u8
-                })
+                linear(t*t*(3.0 - 2.0*t))
             },
-            Interpolation::Cubic =>
+            Interpolation::Bias(bias) =>
             {
-                self.interpolate_inner(other, |lhs, rhs|
-                {
-                    todo!()
-                })
-            }
+                //below 0.5 stays on this color longer, above 0.5 on the other one
+                let t = amount.clamp(0.0, 1.0);
+
+                linear(t / ((1.0 / bias - 2.0)*(1.0 - t) + 1.0))
+            },
+            Interpolation::LinearGamma(gamma) =>
+            {
+                let to_linear = |value: u8| (value as f32 / 255.0).powf(*gamma);
+
+                let (lhs, rhs) = (to_linear(lhs), to_linear(rhs));
+                let result = lhs + (rhs - lhs)*amount;
+
+                (result.powf(1.0 / gamma) * 255.0).round().clamp(0.0, 255.0) as u8
+            },
+            //the rest only make sense for whole colors, a single channel just goes straight
+            Interpolation::Linear
+                | Interpolation::Cubic
+                | Interpolation::Hsv
+                | Interpolation::Oklch => linear(amount)
         }
     }
 
-    fn interpolate_inner<F: FnMut(u8, u8) -> u8>(&self, other: &Color, mut interp: F) -> Color
+    fn blend_colors(
+        &self,
+        before: &Color,
+        lhs: &Color,
+        rhs: &Color,
+        after: &Color,
+        amount: f32,
+        mut rng: &mut dyn RngCore
+        ) -> Color
     {
-        Color{
-            r: interp(self.r, other.r),
-            g: interp(self.g, other.g),
-            b: interp(self.b, other.b)
-            }
+        match self
+        {
+            Interpolation::Random => lhs.interpolate_random(rhs, &mut rng),
+            Interpolation::Cubic => lhs.interpolate_cubic(before, rhs, after, amount),
+            Interpolation::Hsv => lhs.interpolate_hsv(rhs, amount),
+            Interpolation::Oklch => lhs.interpolate_oklch(rhs, amount),
+            _ => lhs.interpolate_inner(*rhs, |lhs, rhs| self.blend(lhs, rhs, amount))
+        }
     }
 }
 
-impl TryFrom<[&str; 3]> for Color
+#[derive(Clone)]
+pub enum Easing
 {
-    type Error = String;
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    Sine
+}
 
-    fn try_from(item: [&str; 3]) -> Result<Self, Self::Error>
+impl Easing
+{
+    pub fn apply(&self, t: f32) -> f32
     {
-        let parse = |color: &str| -> Result<u8, String>
+        let t = t.clamp(0.0, 1.0);
+
+        let eased = match self
         {
-            color.trim().parse().map_err(|_| format!("error parsing {}", color))
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::EaseInOut =>
+            {
+                if t<0.5
+                {
+                    2.0 * t * t
+                } else
+                {
+                    1.0 - 2.0 * (1.0 - t) * (1.0 - t)
+                }
+            },
+            Easing::Sine => (1.0 - (t * std::f32::consts::PI).cos()) / 2.0
         };
 
-        Ok(Color{
-            r: parse(item[0])?,
-            g: parse(item[1])?,
-            b: parse(item[2])?
-            })
+        eased.clamp(0.0, 1.0)
     }
 }
 
-impl fmt::Display for Color
+impl TryFrom<&str> for Easing
 {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    type Error = String;
+
+    fn try_from(name: &str) -> Result<Self, Self::Error>
     {
-        write!(f, "{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+        match name.to_lowercase().as_str()
+        {
+            "linear" => Ok(Easing::Linear),
+            "ease-in" => Ok(Easing::EaseIn),
+            "ease-out" => Ok(Easing::EaseOut),
+            "ease-in-out" => Ok(Easing::EaseInOut),
+            "sine" => Ok(Easing::Sine),
+            _ => Err(format!("{name} is not a valid easing"))
+        }
     }
 }
 
-
-pub struct Colorer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color
 {
-    colors: Vec<Color>,
-    shift: Option<f32>,
-    interpolation: Interpolation,
-    repeat: f32
+    r: u8,
+    g: u8,
+    b: u8,
+    a: u8
 }
 
-impl Colorer
+impl Color
 {
-    pub fn new(colors: Vec<Color>, shift: bool, interpolation: Interpolation, repeat: f32) -> Self
+    pub fn new(r: u8, g: u8, b: u8) -> Self
     {
-        if colors.is_empty()
+        Color{r, g, b, a: 255}
+    }
+
+    //terraria cant show transparency, alpha only matters when blending toward a
+    //background color with composite
+    pub fn with_alpha(r: u8, g: u8, b: u8, a: u8) -> Self
+    {
+        Color{r, g, b, a}
+    }
+
+    pub fn r(&self) -> u8
+    {
+        self.r
+    }
+
+    pub fn g(&self) -> u8
+    {
+        self.g
+    }
+
+    pub fn b(&self) -> u8
+    {
+        self.b
+    }
+
+    pub fn a(&self) -> u8
+    {
+        self.a
+    }
+
+    //blends toward the background by how transparent this color is, the result is opaque
+    pub fn composite(&self, background: &Color) -> Color
+    {
+        let alpha = self.a as f32 / 255.0;
+
+        let blend = |lhs: u8, rhs: u8|
         {
-            panic!("colors cannot be empty");
+            (lhs as f32*alpha + rhs as f32*(1.0 - alpha)).round() as u8
+        };
+
+        Color::new(blend(self.r, background.r), blend(self.g, background.g), blend(self.b, background.b))
+    }
+
+    pub fn from_hex(text: &str) -> Result<Self, String>
+    {
+        let digits = text.strip_prefix('#')
+            .ok_or_else(|| format!("{text} doesnt start with #"))?;
+
+        if let Some(c) = digits.chars().find(|c| !c.is_ascii_hexdigit())
+        {
+            return Err(format!("{text} has a non hex digit {c}"));
         }
 
-        let shift = if shift
+        let parse = |digits: &str| u8::from_str_radix(digits, 16).unwrap();
+
+        match digits.len()
         {
-            Some(0.0)
-        } else
+            3 | 4 =>
+            {
+                //#rgb is shorthand for #rrggbb
+                let channel = |index: usize|
+                {
+                    digits.get(index..index+1).map_or(255, |digit| parse(digit) * 17)
+                };
+
+                Ok(Color::with_alpha(channel(0), channel(1), channel(2), channel(3)))
+            },
+            6 | 8 =>
+            {
+                let channel = |index: usize|
+                {
+                    digits.get(index*2..index*2+2).map_or(255, parse)
+                };
+
+                Ok(Color::with_alpha(channel(0), channel(1), channel(2), channel(3)))
+            },
+            len => Err(format!("{text} has {len} digits, expected 3, 4, 6 or 8"))
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self>
+    {
+        let color = match name.to_lowercase().as_str()
         {
-            None
+            "red" => Color::new(255, 0, 0),
+            "green" => Color::new(0, 128, 0),
+            "blue" => Color::new(0, 0, 255),
+            "cyan" => Color::new(0, 255, 255),
+            "magenta" => Color::new(255, 0, 255),
+            "yellow" => Color::new(255, 255, 0),
+            "white" => Color::new(255, 255, 255),
+            "black" => Color::new(0, 0, 0),
+            "gray" | "grey" => Color::new(128, 128, 128),
+            "orange" => Color::new(255, 165, 0),
+            "purple" => Color::new(128, 0, 128),
+            "pink" => Color::new(255, 192, 203),
+            "brown" => Color::new(165, 42, 42),
+            "gold" => Color::new(255, 215, 0),
+            "lime" => Color::new(0, 255, 0),
+            "navy" => Color::new(0, 0, 128),
+            "teal" => Color::new(0, 128, 128),
+            "violet" => Color::new(238, 130, 238),
+            _ => return None
         };
 
-        let mut out = Colorer{colors, shift, interpolation, repeat};
-        out.word();
+        Some(color)
+    }
 
-        out
+    pub fn interpolate(&self, other: &Color, amount: f32, interpolator: &dyn Interpolator) -> Color
+    {
+        //no neighbors here so the endpoints act as their own control points
+        interpolator.blend_colors(self, self, other, other, amount, &mut rand::thread_rng())
     }
 
-    pub fn color_text(&mut self, text: &str) -> String
+    fn interpolate_hsv(&self, other: &Color, amount: f32) -> Color
     {
-        let chars_amount = text.chars().count();
+        let (lhs_h, lhs_s, lhs_v) = self.to_hsv();
+        let (rhs_h, rhs_s, rhs_v) = other.to_hsv();
+
+        //go around the shorter way of the hue circle
+        let mut hue_diff = rhs_h - lhs_h;
+        if hue_diff>180.0
+        {
+            hue_diff -= 360.0;
+        } else if hue_diff< -180.0
+        {
+            hue_diff += 360.0;
+        }
 
-        let mut new_message = String::new();
+        let lerp = |lhs: f32, rhs: f32| lhs + (rhs - lhs)*amount;
 
-        let mut index = 0;
-        let mut ignore = false;
+        Color::from_hsv(
+            lhs_h + hue_diff*amount,
+            lerp(lhs_s, rhs_s),
+            lerp(lhs_v, rhs_v)
+            ).alpha_between(self, other, amount)
+    }
+
+    fn interpolate_oklch(&self, other: &Color, amount: f32) -> Color
+    {
+        let to_lch = |[l, a, b]: [f32; 3]| (l, a.hypot(b), b.atan2(a));
+
+        let (lhs_l, lhs_c, mut lhs_h) = to_lch(self.to_oklab());
+        let (rhs_l, rhs_c, mut rhs_h) = to_lch(other.to_oklab());
 
-        if let Some(color) = self.solid()
+        //grays dont have a hue so they take it from the other side
+        const GRAY_CHROMA: f32 = 0.0001;
+        if lhs_c<GRAY_CHROMA
         {
-            let color_string = format!("[c/{color}:");
+            lhs_h = rhs_h;
+        } else if rhs_c<GRAY_CHROMA
+        {
+            rhs_h = lhs_h;
+        }
 
-            let mut iter = text.chars().peekable();
+        let mut hue_diff = rhs_h - lhs_h;
+        if hue_diff>std::f32::consts::PI
+        {
+            hue_diff -= std::f32::consts::TAU;
+        } else if hue_diff< -std::f32::consts::PI
+        {
+            hue_diff += std::f32::consts::TAU;
+        }
 
-            ignore = iter.peek().map_or(true, |val| *val=='[');
-            if !ignore
-            {
-                new_message.push_str(&color_string);
-            }
+        let lerp = |lhs: f32, rhs: f32| lhs + (rhs - lhs)*amount;
 
-            while let Some(c) = iter.next()
-            {
-                if c=='['
-                {
-                    if !ignore
-                    {
-                        new_message.push(']');
-                    }
-                    new_message.push(c);
-                    ignore = true;
-                } else
-                {
-                    new_message.push(c);
+        let l = lerp(lhs_l, rhs_l);
+        let c = lerp(lhs_c, rhs_c);
+        let h = lhs_h + hue_diff*amount;
 
-                    if c==']' && iter.peek().map_or(false, |val| *val!='[')
-                    {
-                        new_message.push_str(&color_string);
-                        ignore = false;
-                    }
-                }
-            }
+        Color::from_oklab([l, c * h.cos(), c * h.sin()]).alpha_between(self, other, amount)
+    }
 
-            if !ignore
+    pub fn to_oklab(&self) -> [f32; 3]
+    {
+        let to_linear = |value: u8|
+        {
+            let value = value as f32 / 255.0;
+
+            if value<=0.04045
+            {
+                value / 12.92
+            } else
             {
-                new_message.push(']');
+                ((value + 0.055) / 1.055).powf(2.4)
             }
-        } else
+        };
+
+        let (r, g, b) = (to_linear(self.r), to_linear(self.g), to_linear(self.b));
+
+        let l = (0.41222147 * r + 0.53633254 * g + 0.051445993 * b).cbrt();
+        let m = (0.2119035 * r + 0.6806995 * g + 0.10739696 * b).cbrt();
+        let s = (0.08830246 * r + 0.28171884 * g + 0.6299787 * b).cbrt();
+
+        [
+            0.21045426 * l + 0.7936178 * m - 0.004072047 * s,
+            1.9779985 * l - 2.4285922 * m + 0.4505937 * s,
+            0.025904037 * l + 0.78277177 * m - 0.80867577 * s
+        ]
+    }
+
+    //colors outside of srgb get clamped into it
+    pub fn from_oklab([lightness, a, b]: [f32; 3]) -> Color
+    {
+        let l = (lightness + 0.39633778 * a + 0.21580376 * b).powi(3);
+        let m = (lightness - 0.105561346 * a - 0.06385417 * b).powi(3);
+        let s = (lightness - 0.08948418 * a - 1.2914855 * b).powi(3);
+
+        let from_linear = |value: f32|
         {
-            //signal that its a new message
-            self.word();
-            for c in text.chars()
+            let value = value.clamp(0.0, 1.0);
+
+            let value = if value<=0.0031308
             {
-                if c=='['
-                {
-                    ignore = true;
-                }
+                value * 12.92
+            } else
+            {
+                1.055 * value.powf(1.0 / 2.4) - 0.055
+            };
 
-                if !ignore
-                {
-                    let position = index as f32/chars_amount as f32;
+            (value * 255.0).round().clamp(0.0, 255.0) as u8
+        };
 
-                    let colored = self.color(c, position);
+        Color::new(
+            from_linear(4.0767417 * l - 3.3077116 * m + 0.23096993 * s),
+            from_linear(-1.268438 * l + 2.6097574 * m - 0.3413194 * s),
+            from_linear(-0.0041960863 * l - 0.7034186 * m + 1.7076147 * s)
+            )
+    }
 
-                    new_message.push_str(colored.as_str());
+    //evenly spaced hues at full saturation and value, the last stop doesnt
+    //repeat red so shifted gradients loop around seamlessly
+    pub fn rainbow(stops: usize) -> Vec<Color>
+    {
+        (0..stops).map(|index|
+        {
+            Color::from_hsv(index as f32 * 360.0 / stops as f32, 1.0, 1.0)
+        }).collect()
+    }
 
-                    index += 1;
-                } else
-                {
-                    new_message.push(c);
-                }
+    //colors the game itself uses in chat, for team chat and item rarities
+    pub fn game_palette() -> Vec<Color>
+    {
+        [
+            (255, 255, 255), (255, 240, 20), (130, 130, 130),
+            (218, 59, 59), (59, 218, 85), (59, 149, 218), (242, 221, 100), (224, 100, 242),
+            (150, 150, 255), (150, 255, 150), (255, 200, 150), (255, 150, 150),
+            (255, 150, 255), (210, 160, 255), (150, 255, 10), (255, 255, 10),
+            (5, 200, 255), (255, 40, 100), (180, 40, 255)
+        ].into_iter().map(Color::from).collect()
+    }
 
-                if c==']'
-                {
-                    ignore = false;
-                }
-            }
-        }
+    //closest by how different they look, which rgb distance gets wrong around greens and blues
+    pub fn nearest<'a>(&self, palette: &'a [Color]) -> Option<&'a Color>
+    {
+        let lab = self.to_oklab();
+        let distance = |color: &Color|
+        {
+            color.to_oklab().into_iter().zip(lab)
+                .map(|(lhs, rhs)| (lhs - rhs).powi(2))
+                .sum::<f32>()
+        };
 
-        new_message
+        palette.iter().min_by(|lhs, rhs| distance(lhs).total_cmp(&distance(rhs)))
     }
 
-    fn solid(&self) -> Option<Color>
+    //like hsv(h,s,v) with the hue in degrees and saturation and value from 0 to 100
+    fn from_hsv_text(text: &str) -> Result<Color, String>
     {
-        if self.colors.len()==1
+        let inner = text.get(4..)
+            .and_then(|inner| inner.strip_suffix(')'))
+            .ok_or_else(|| format!("{text} is missing its closing )"))?;
+
+        let values = inner.split(',').map(|value|
         {
-            Some(self.colors[0].clone())
-        } else
+            value.trim().parse::<f32>().map_err(|err| format!("{err} cannot be converted to a number in {text}"))
+        }).collect::<Result<Vec<_>, _>>()?;
+
+        let [hue, saturation, value] = values[..]
+        else
         {
-            None
+            return Err(format!("{text} needs a hue, saturation and value"));
+        };
+
+        if !(0.0..=360.0).contains(&hue)
+        {
+            return Err(format!("hue {hue} is outside of 0 to 360"));
         }
-    }
 
-    fn word(&mut self)
-    {
-        if self.shift.is_some()
+        for (name, amount) in [("saturation", saturation), ("value", value)]
         {
-            self.shift = Some(rand::random());
+            if !(0.0..=100.0).contains(&amount)
+            {
+                return Err(format!("{name} {amount} is outside of 0 to 100"));
+            }
         }
+
+        Ok(Color::from_hsv(hue, saturation / 100.0, value / 100.0))
     }
 
-    fn color(&self, c: char, position: f32) -> String
+    //hue in degrees, saturation and value in 0..=1
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Color
     {
-        if c==' '
+        let hue = hue.rem_euclid(360.0);
+
+        let chroma = value*saturation;
+        let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+        let m = value - chroma;
+
+        let (r, g, b) = match (hue / 60.0) as u32
         {
-            return c.to_string();
-        }
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x)
+        };
+
+        let channel = |value: f32| ((value + m) * 255.0).round().clamp(0.0, 255.0) as u8;
 
-        let color = if self.colors.len()==1
+        Color::new(channel(r), channel(g), channel(b))
+    }
+
+    pub fn rotate_hue(&self, degrees: f32) -> Color
+    {
+        let (hue, saturation, value) = self.to_hsv();
+
+        Color{a: self.a, ..Color::from_hsv(hue + degrees, saturation, value)}
+    }
+
+    pub fn to_hsv(&self) -> (f32, f32, f32)
+    {
+        let r = self.r as f32 / 255.0;
+        let g = self.g as f32 / 255.0;
+        let b = self.b as f32 / 255.0;
+
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+
+        let hue = if delta==0.0
+        {
+            0.0
+        } else if max==r
         {
-            self.colors[0].clone()
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max==g
+        {
+            60.0 * ((b - r) / delta + 2.0)
         } else
         {
-            let mut position = position*self.repeat;
+            60.0 * ((r - g) / delta + 4.0)
+        };
 
-            if let Some(amount) = self.shift
-            {
-                position += amount
-            }
+        let saturation = if max==0.0
+        {
+            0.0
+        } else
+        {
+            delta / max
+        };
 
-            if position>=self.repeat
-            {
-                position -= self.repeat;
-            }
+        (hue, saturation, max)
+    }
 
-            let max_val = if self.shift.is_none()
+    pub fn interpolate_random(&self, other: &Color, rng: &mut impl Rng) -> Color
+    {
+        self.interpolate_inner(*other, |lhs, rhs|
+        {
+            if rng.gen::<f32>()<0.5
             {
-                self.colors.len()-1
+                lhs
             } else
             {
-                self.colors.len()
-            };
+                rhs
+            }
+        })
+    }
+
+    pub fn interpolate_cubic(
+        &self,
+        before: &Color,
+        other: &Color,
+        after: &Color,
+        amount: f32
+        ) -> Color
+    {
+        let catmull_rom = |p0: u8, p1: u8, p2: u8, p3: u8| -> u8
+        {
+            let (p0, p1, p2, p3) = (p0 as f32, p1 as f32, p2 as f32, p3 as f32);
+
+            let t = amount;
+            let t2 = t * t;
+            let t3 = t2 * t;
 
-            let color_position = max_val as f32 * position;
+            let result = 0.5 * (
+                2.0 * p1
+                + (p2 - p0) * t
+                + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+                + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3
+                );
 
-            self.interpolate(
-                color_position.floor() as usize % self.colors.len(),
-                color_position.ceil() as usize % self.colors.len(),
-                color_position.fract()
-                )
+            //catmull rom can overshoot past the control points
+            result.round().clamp(0.0, 255.0) as u8
         };
 
-        format!("[c/{color}:{c}]")
+        Color{
+            r: catmull_rom(before.r, self.r, other.r, after.r),
+            g: catmull_rom(before.g, self.g, other.g, after.g),
+            b: catmull_rom(before.b, self.b, other.b, after.b),
+            a: catmull_rom(before.a, self.a, other.a, after.a)
+            }
     }
 
-    fn interpolate(&self, left: usize, mut right: usize, amount: f32) -> Color
+    //same as try_from except values outside of 0 to 255 get clamped into it
+    pub fn from_clamped(item: [&str; 3]) -> Result<Color, String>
+    {
+        let parse = |color: &str| -> Result<u8, String>
+        {
+            Ok(Color::parse_channel(color)?.clamp(0, 255) as u8)
+        };
+
+        Ok(Color::new(parse(item[0])?, parse(item[1])?, parse(item[2])?))
+    }
+
+    fn parse_channel(color: &str) -> Result<i32, String>
+    {
+        color.trim().parse().map_err(|_| format!("error parsing {}", color))
+    }
+
+    fn interpolate_inner<F: FnMut(u8, u8) -> u8>(self, other: Color, mut interp: F) -> Color
+    {
+        Color{
+            r: interp(self.r, other.r),
+            g: interp(self.g, other.g),
+            b: interp(self.b, other.b),
+            a: interp(self.a, other.a)
+            }
+    }
+
+    //appends [c/rrggbb:c] without going through the formatting machinery
+    pub fn write_tag(&self, out: &mut String, c: char)
+    {
+        out.push_str("[c/");
+        self.write_hex(out);
+        out.push(':');
+        out.push(c);
+        out.push(']');
+    }
+
+    //same as display but straight into the string
+    fn write_hex(&self, out: &mut String)
+    {
+        const DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+        for channel in [self.r, self.g, self.b]
+        {
+            out.push(DIGITS[(channel >> 4) as usize] as char);
+            out.push(DIGITS[(channel & 0xf) as usize] as char);
+        }
+    }
+
+    fn alpha_between(mut self, lhs: &Color, rhs: &Color, amount: f32) -> Color
+    {
+        let diff = rhs.a as f32 - lhs.a as f32;
+        self.a = (lhs.a as f32 + diff*amount).round() as u8;
+
+        self
+    }
+}
+
+/// ```
+/// use terraria_cool_colorer::Color;
+///
+/// assert_eq!(Color::from((255, 0, 0)), Color::new(255, 0, 0));
+/// ```
+impl From<(u8, u8, u8)> for Color
+{
+    fn from((r, g, b): (u8, u8, u8)) -> Self
+    {
+        Color::new(r, g, b)
+    }
+}
+
+/// ```
+/// use terraria_cool_colorer::Color;
+///
+/// assert_eq!(Color::from([255, 0, 0]), Color::new(255, 0, 0));
+/// ```
+impl From<[u8; 3]> for Color
+{
+    fn from([r, g, b]: [u8; 3]) -> Self
+    {
+        Color::new(r, g, b)
+    }
+}
+
+impl TryFrom<[&str; 3]> for Color
+{
+    type Error = String;
+
+    fn try_from(item: [&str; 3]) -> Result<Self, Self::Error>
+    {
+        let parse = |color: &str| -> Result<u8, String>
+        {
+            let value = Color::parse_channel(color)?;
+
+            u8::try_from(value).map_err(|_| format!("{value} is outside of the 0 to 255 range"))
+        };
+
+        Ok(Color::new(parse(item[0])?, parse(item[1])?, parse(item[2])?))
+    }
+}
+
+//accepts r,g,b, #rrggbb, #rgb (with an optional alpha digit or two), hsv(h,s,v) or a color name
+impl FromStr for Color
+{
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err>
+    {
+        let text = text.trim();
+
+        if text.starts_with('#')
+        {
+            return Color::from_hex(text);
+        }
+
+        if text.to_lowercase().starts_with("hsv(")
+        {
+            return Color::from_hsv_text(text);
+        }
+
+        if text.starts_with(|c: char| c.is_alphabetic())
+        {
+            return Color::from_name(text).ok_or_else(|| format!("unknown color name: {text}"));
+        }
+
+        match text.split(',').collect::<Vec<_>>().as_slice()
+        {
+            [r, g, b] => Color::try_from([*r, *g, *b]),
+            _ => Err(format!("{text} is not a color, colors are r,g,b, #rrggbb, #rgb, hsv(h,s,v) or a name"))
+        }
+    }
+}
+
+impl fmt::Display for Color
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+
+//how a character ends up in the colored message
+enum Styled
+{
+    Colored(Color),
+    Space,
+    Raw
+}
+
+#[derive(Clone)]
+pub enum RepeatMode
+{
+    Wrap,
+    Mirror
+}
+
+impl TryFrom<&str> for RepeatMode
+{
+    type Error = String;
+
+    fn try_from(name: &str) -> Result<Self, Self::Error>
+    {
+        match name.to_lowercase().as_str()
+        {
+            "wrap" => Ok(RepeatMode::Wrap),
+            "mirror" => Ok(RepeatMode::Mirror),
+            _ => Err(format!("{name} is not a valid repeat mode"))
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ColorerSettings
+{
+    pub shift: bool,
+    pub shift_amount: f32,
+    pub interpolation: Arc<dyn Interpolator>,
+    pub steps: Option<u32>,
+    pub repeat: f32,
+    pub repeat_mode: RepeatMode,
+    pub color_commands: bool,
+    pub per_word: bool,
+    pub color_spaces: bool,
+    pub newline_reset: bool,
+    pub strip_control: bool,
+    pub seed: Option<u64>,
+    pub easing: Easing,
+    pub reverse: bool,
+    pub animate: Option<f32>,
+    pub fade_to: Option<Color>,
+    pub jitter: u8,
+    pub hue_shift: f32,
+    pub palette: Option<Vec<Color>>,
+    pub positions: Option<Vec<f32>>,
+    pub accent: Option<usize>
+}
+
+impl Default for ColorerSettings
+{
+    fn default() -> Self
+    {
+        ColorerSettings{
+            shift: true,
+            shift_amount: 1.0,
+            interpolation: Arc::new(Interpolation::Linear),
+            steps: None,
+            repeat: 1.0,
+            repeat_mode: RepeatMode::Wrap,
+            color_commands: false,
+            per_word: false,
+            color_spaces: false,
+            newline_reset: true,
+            strip_control: false,
+            seed: None,
+            easing: Easing::Linear,
+            reverse: false,
+            animate: None,
+            fade_to: None,
+            jitter: 0,
+            hue_shift: 0.0,
+            palette: None,
+            positions: None,
+            accent: None
+            }
+    }
+}
+
+pub struct Colorer
+{
+    colors: Vec<Color>,
+    positions: Option<Vec<f32>>,
+    shift: Option<f32>,
+    shift_amount: f32,
+    interpolation: Arc<dyn Interpolator>,
+    steps: Option<u32>,
+    repeat: f32,
+    repeat_mode: RepeatMode,
+    color_commands: bool,
+    per_word: bool,
+    color_spaces: bool,
+    newline_reset: bool,
+    strip_control: bool,
+    easing: Easing,
+    animate: Option<f32>,
+    fade_to: Option<Color>,
+    jitter: u8,
+    hue_shift: f32,
+    palette: Option<Vec<Color>>,
+    accent: Option<usize>,
+    start: Instant,
+    rng: StdRng
+}
+
+impl Colorer
+{
+    pub fn new(mut colors: Vec<Color>, settings: ColorerSettings) -> Self
+    {
+        if colors.is_empty()
+        {
+            panic!("colors cannot be empty");
+        }
+
+        let mut positions = settings.positions;
+
+        //stops are at these positions instead of evenly spaced, ascending from 0 to 1
+        if positions.as_ref().is_some_and(|positions| positions.len()!=colors.len())
+        {
+            panic!("positions need one position for every color");
+        }
+
+        //the first band is at the start and the last at the end so one band has nowhere to go
+        if settings.steps.is_some_and(|steps| steps<2)
+        {
+            panic!("steps need to be at least 2");
+        }
+
+        //running the gradient backwards is the same as flipping the stops
+        if settings.reverse
+        {
+            colors.reverse();
+
+            if let Some(positions) = positions.as_mut()
+            {
+                positions.reverse();
+                positions.iter_mut().for_each(|position| *position = 1.0 - *position);
+            }
+        }
+
+        //no room to shift in is the same as not shifting at all
+        let shifts = settings.shift && settings.shift_amount>0.0;
+
+        let shift = if shifts || settings.animate.is_some()
+        {
+            Some(0.0)
+        } else
+        {
+            None
+        };
+
+        let rng = settings.seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64);
+
+        let mut out = Colorer{
+            colors,
+            positions,
+            shift,
+            shift_amount: settings.shift_amount,
+            interpolation: settings.interpolation,
+            steps: settings.steps,
+            repeat: settings.repeat,
+            repeat_mode: settings.repeat_mode,
+            color_commands: settings.color_commands,
+            per_word: settings.per_word,
+            color_spaces: settings.color_spaces,
+            newline_reset: settings.newline_reset,
+            strip_control: settings.strip_control,
+            easing: settings.easing,
+            animate: settings.animate,
+            fade_to: settings.fade_to,
+            jitter: settings.jitter,
+            hue_shift: settings.hue_shift,
+            palette: settings.palette,
+            accent: settings.accent,
+            start: Instant::now(),
+            rng
+            };
+        out.word();
+
+        out
+    }
+
+    pub fn color_text(&mut self, text: &str) -> String
+    {
+        let text = self.prepared(text);
+
+        let Some((colored, rest)) = self.split_colored(&text)
+        else
+        {
+            return text.into_owned();
+        };
+
+        self.color_span(colored) + rest
+    }
+
+    fn prepared<'a>(&self, text: &'a str) -> Cow<'a, str>
+    {
+        if self.strip_control
+        {
+            Cow::Owned(Self::without_control(text))
+        } else
+        {
+            Cow::Borrowed(text)
+        }
+    }
+
+    //line breaks turn into a space so the words around them dont get glued together
+    fn without_control(text: &str) -> String
+    {
+        text.replace("\r\n", " ").chars().filter_map(|c|
+        {
+            match c
+            {
+                '\r' | '\n' => Some(' '),
+                c if c.is_control() => None,
+                c => Some(c)
+            }
+        }).collect()
+    }
+
+    //the part of a message that gets colored and the rest after it thats left as it is,
+    //none if the whole message is left alone
+    fn split_colored<'a>(&mut self, text: &'a str) -> Option<(&'a str, &'a str)>
+    {
+        //nothing to color, also keeps the gradient positions from dividing by zero
+        if text.trim().is_empty()
+        {
+            return None;
+        }
+
+        //commands stop working if they get wrapped in tags
+        if !self.color_commands && text.trim_start().starts_with('/')
+        {
+            return None;
+        }
+
+        //signal that its a new message, without the reset the shift rolled when the colorer
+        //was made carries on through every message
+        if self.newline_reset || self.animate.is_some()
+        {
+            self.word();
+        }
+
+        let split = self.accent.map_or(text.len(), |accent| Self::accent_end(text, accent));
+
+        Some(text.split_at(split))
+    }
+
+    //a color from a random spot along the gradient, for things that arent text
+    pub fn random_color(&mut self) -> Color
+    {
+        let position = self.rng.gen();
+
+        self.color_at(position)
+    }
+
+    //where the accented part ends, after at least length letters and then the rest
+    //of the word theyre in, tags dont count and are never split
+    fn accent_end(text: &str, length: usize) -> usize
+    {
+        let mut counted = 0;
+        for ((index, c), in_tag) in text.char_indices().zip(Self::tag_mask(text))
+        {
+            if in_tag
+            {
+                continue;
+            }
+
+            if c==' '
+            {
+                if counted>=length
+                {
+                    return index;
+                }
+            } else
+            {
+                counted += 1;
+            }
+        }
+
+        text.len()
+    }
+
+    //the color of every character color_text would send, characters that are left as they are
+    //(spaces, existing tags, escaped characters, commands and whats after the accent) dont have one
+    pub fn color_chars(&mut self, text: &str) -> Vec<(char, Option<Color>)>
+    {
+        let text = self.prepared(text);
+
+        let (colored, rest) = self.split_colored(&text).unwrap_or(("", &text));
+
+        let mut chars: Vec<_> = if colored.is_empty()
+        {
+            Vec::new()
+        } else
+        {
+            self.styled_chars(colored).into_iter().map(|(c, styled)|
+            {
+                match styled
+                {
+                    Styled::Colored(color) => (c, Some(color)),
+                    Styled::Space | Styled::Raw => (c, None)
+                }
+            }).collect()
+        };
+
+        chars.extend(rest.chars().map(|c| (c, None)));
+
+        chars
+    }
+
+    fn styled_chars(&mut self, text: &str) -> Vec<(char, Styled)>
+    {
+        let in_tag = Self::tag_mask(text);
+
+        //only the characters that get colored count towards the gradient
+        let colorable: Vec<char> = text.chars().zip(&in_tag)
+            .filter(|(_, in_tag)| !**in_tag)
+            .map(|(c, _)| c)
+            .collect();
+
+        let mut positions = self.positions(&colorable).into_iter();
+
+        let solid = self.solid();
+
+        let mut previous = None;
+        text.chars().zip(in_tag).map(|(c, in_tag)|
+        {
+            let position = (!in_tag).then(|| positions.next().unwrap());
+
+            //a backslash escapes whatever comes after it, so that stays as it is
+            //and a lone backslash in a tag would escape its closing bracket
+            //control characters like line breaks go through as they are so theyre never inside a tag
+            let raw = in_tag || previous==Some('\\') || c=='\\' || c.is_control();
+
+            previous = Some(c);
+
+            let styled = if c==' ' && !in_tag && !self.color_spaces
+            {
+                Styled::Space
+            } else if raw
+            {
+                Styled::Raw
+            } else
+            {
+                Styled::Colored(solid.unwrap_or_else(|| self.color_at(position.unwrap())))
+            };
+
+            (c, styled)
+        }).collect()
+    }
+
+    //a single color with nothing random on top is the same for every character,
+    //so its worked out once per message instead
+    fn solid(&mut self) -> Option<Color>
+    {
+        (self.colors.len()==1 && self.jitter==0).then(|| self.color_at(0.0))
+    }
+
+    fn color_span(&mut self, text: &str) -> String
+    {
+        //close to the worst case of every character getting its own tag
+        let mut new_message = String::with_capacity(text.len() * (TAG_LENGTH + 1));
+
+        //characters next to each other with the same color share a single tag,
+        //this is the color of the tag thats currently open
+        let mut run: Option<Color> = None;
+
+        //spaces arent colored so they only join a run if it keeps going after them
+        let mut spaces = String::new();
+
+        for (c, styled) in self.styled_chars(text)
+        {
+            let color = match styled
+            {
+                Styled::Space =>
+                {
+                    spaces.push(c);
+                    continue;
+                },
+                Styled::Raw =>
+                {
+                    Self::close_run(&mut new_message, run.take());
+                    new_message.push_str(&spaces);
+                    spaces.clear();
+
+                    new_message.push(c);
+                    continue;
+                },
+                Styled::Colored(color) => color
+            };
+
+            if run==Some(color)
+            {
+                new_message.push_str(&spaces);
+            } else
+            {
+                Self::close_run(&mut new_message, run.take());
+                new_message.push_str(&spaces);
+
+                new_message.push_str("[c/");
+                color.write_hex(&mut new_message);
+                new_message.push(':');
+                run = Some(color);
+            }
+
+            spaces.clear();
+
+            //brackets outside of tags are just text, escaped so they cant close
+            //or open tags and break the formatting
+            if c=='[' || c==']'
+            {
+                new_message.push('\\');
+            }
+
+            new_message.push(c);
+        }
+
+        Self::close_run(&mut new_message, run);
+        new_message.push_str(&spaces);
+
+        new_message
+    }
+
+    fn positions(&self, colorable: &[char]) -> Vec<f32>
+    {
+        let spread = |length: usize| (0..length).map(move |index| index as f32/length as f32);
+
+        if !self.per_word
+        {
+            return spread(colorable.len()).collect();
+        }
+
+        let mut positions = Vec::with_capacity(colorable.len());
+
+        let mut start = 0;
+        while start<colorable.len()
+        {
+            let end = colorable[start..].iter().position(|c| *c==' ')
+                .map_or(colorable.len(), |position| start + position);
+
+            positions.extend(spread(end - start));
+
+            //the space itself, which is only colored with --color-spaces
+            if end<colorable.len()
+            {
+                positions.push(0.0);
+            }
+
+            start = end + 1;
+        }
+
+        positions
+    }
+
+    //existing tags like [i:29] or [c/ff0000:text] are passed through untouched
+    //so they still work, true for every character thats inside of one
+    fn tag_mask(text: &str) -> Vec<bool>
+    {
+        let chars: Vec<char> = text.chars().collect();
+        let mut mask = vec![false; chars.len()];
+
+        let mut index = 0;
+        while index<chars.len()
+        {
+            let escaped = index>0 && chars[index-1]=='\\';
+
+            match Self::tag_length(&chars[index..]).filter(|_| !escaped)
+            {
+                Some(length) =>
+                {
+                    mask[index..index+length].fill(true);
+                    index += length;
+                },
+                None => index += 1
+            }
+        }
+
+        mask
+    }
+
+    //same rules as terraria uses: [name/options:text] where the name is 1 to 10 letters,
+    //options are optional and the text ends at the first ] that isnt escaped
+    fn tag_length(text: &[char]) -> Option<usize>
+    {
+        if text.first()!=Some(&'[')
+        {
+            return None;
+        }
+
+        let name_length = text[1..].iter().take_while(|c| c.is_ascii_alphabetic()).count();
+        if !(1..=10).contains(&name_length)
+        {
+            return None;
+        }
+
+        let mut index = 1 + name_length;
+
+        if text.get(index)==Some(&'/')
+        {
+            let options_length = text[index+1..].iter().take_while(|c| **c!=':').count();
+            if options_length==0
+            {
+                return None;
+            }
+
+            index += 1 + options_length;
+        }
+
+        if text.get(index)!=Some(&':')
+        {
+            return None;
+        }
+
+        //the text has at least a single character, a tag cut off before its ] isnt
+        //a tag at all so its [ gets escaped like any other text instead of dangling
+        let text_start = index + 1;
+        (text_start+1..text.len())
+            .find(|&index| text[index]==']' && text[index-1]!='\\')
+            .map(|end| end + 1)
+    }
+
+    fn word(&mut self)
+    {
+        if let Some(hz) = self.animate
+        {
+            //goes through the whole gradient hz times a second
+            let cycles = self.start.elapsed().as_secs_f32()*hz;
+
+            self.shift = Some(cycles.fract());
+        } else if self.shift.is_some()
+        {
+            self.shift = Some(self.rng.gen::<f32>() * self.shift_amount);
+        }
+    }
+
+    fn close_run(message: &mut String, run: Option<Color>)
+    {
+        if run.is_some()
+        {
+            message.push(']');
+        }
+    }
+
+    //the color a letter at position (0 to 1 along the text) would get, with the easing,
+    //repeats and the shift of the current word applied, 0 is the first color (after the shift)
+    //and 1 is the end of the last repeat which is back at the first color unless its mirrored,
+    //mut because jitter and the random interpolation draw from the colorers rng
+    pub fn color_at(&mut self, position: f32) -> Color
+    {
+        let position = self.stepped(position);
+
+        let mut color = self.gradient_color(position);
+
+        if self.hue_shift!=0.0
+        {
+            color = color.rotate_hue(self.hue_shift);
+        }
+
+        let color = self.faded(color);
+        let color = self.jittered(color);
+
+        //snapped last so whatever comes out is always one of the palette colors
+        match &self.palette
+        {
+            Some(palette) => color.nearest(palette).copied().unwrap_or(color),
+            None => color
+        }
+    }
+
+    //equally wide bands with the first one at the start of the gradient and the last at the end,
+    //neighbouring letters in a band end up the same color so they share a tag
+    fn stepped(&self, position: f32) -> f32
+    {
+        let Some(steps) = self.steps
+        else
+        {
+            return position;
+        };
+
+        let band = (position * steps as f32).floor().min((steps - 1) as f32);
+
+        //a wrapping gradient ends where it started so the last band cant be at the end
+        let last = if self.wraps()
+        {
+            steps
+        } else
+        {
+            steps - 1
+        };
+
+        //right at the end would already count as wrapped around to the first color
+        (band / last as f32).min(1.0_f32.next_down())
+    }
+
+    fn gradient_color(&mut self, position: f32) -> Color
+    {
+        if self.colors.len()==1
+        {
+            return self.colors[0];
+        }
+
+        let mut position = self.easing.apply(position)*self.repeat;
+
+        if let Some(amount) = self.shift
+        {
+            position += amount
+        }
+
+        match self.repeat_mode
+        {
+            //every repeat starts over from the first color
+            RepeatMode::Wrap => position = position.fract(),
+            RepeatMode::Mirror =>
+            {
+                //every other repeat runs backwards so the ends meet without a seam
+                let cycle = position.rem_euclid(2.0);
+
+                position = if cycle>1.0
+                {
+                    2.0 - cycle
+                } else
+                {
+                    cycle
+                };
+            }
+        }
+
+        if let Some(positions) = &self.positions
+        {
+            let (left, right, amount) = Self::segment(positions, position, self.wraps());
+
+            return self.interpolate(left, right, amount);
+        }
+
+        let max_val = if !self.wraps()
+        {
+            self.colors.len()-1
+        } else
+        {
+            self.colors.len()
+        };
+
+        let color_position = max_val as f32 * position;
+
+        self.interpolate(
+            color_position.floor() as usize % self.colors.len(),
+            color_position.ceil() as usize % self.colors.len(),
+            color_position.fract()
+            )
+    }
+
+    //a shifted gradient goes from the last color back to the first, unless its mirrored
+    fn wraps(&self) -> bool
+    {
+        self.shift.is_some() && matches!(self.repeat_mode, RepeatMode::Wrap)
+    }
+
+    //the stops around a position and how far between them it is
+    fn segment(positions: &[f32], position: f32, wraps: bool) -> (usize, usize, f32)
+    {
+        let last = positions.len() - 1;
+
+        let next = positions.partition_point(|stop| *stop<=position);
+
+        if next==0 || next>last
+        {
+            //before the first stop or after the last one is just that stop
+            if !wraps
+            {
+                let index = next.min(last);
+
+                return (index, index, 0.0);
+            }
+
+            //a shifted gradient goes from the last stop back around to the first one
+            let start = positions[last];
+            let end = positions[0] + 1.0;
+
+            let position = if next==0
+            {
+                position + 1.0
+            } else
+            {
+                position
+            };
+
+            return (last, 0, (position - start) / (end - start));
+        }
+
+        let (start, end) = (positions[next-1], positions[next]);
+
+        (next-1, next, (position - start) / (end - start))
+    }
+
+    //transparent colors fade into the background color, or are just shown opaque without one
+    fn faded(&self, color: Color) -> Color
+    {
+        match &self.fade_to
+        {
+            Some(background) => color.composite(background),
+            None => Color::new(color.r, color.g, color.b)
+        }
+    }
+
+    //nudges every channel by up to the jitter amount either way
+    fn jittered(&mut self, color: Color) -> Color
+    {
+        if self.jitter==0
+        {
+            return color;
+        }
+
+        let jitter = self.jitter as i32;
+        let mut nudge = |value: u8|
+        {
+            (value as i32 + self.rng.gen_range(-jitter..=jitter)).clamp(0, 255) as u8
+        };
+
+        Color::with_alpha(nudge(color.r), nudge(color.g), nudge(color.b), color.a)
+    }
+
+    fn interpolate(&mut self, left: usize, mut right: usize, amount: f32) -> Color
     {
         if right>=self.colors.len()
         {
-            //could subtract self.colors.len() but it should never be more than len
-            right = 0;
+            //could subtract self.colors.len() but it should never be more than len
+            right = 0;
+        }
+
+        let len = self.colors.len();
+
+        //a shifted gradient wraps around so its neighbors do too, otherwise
+        //the first and last stops are their own neighbors past the ends
+        let (before, after) = if self.wraps()
+        {
+            ((left + len - 1) % len, (left + 2) % len)
+        } else
+        {
+            (left.saturating_sub(1), (left + 2).min(len - 1))
+        };
+
+        self.interpolation.blend_colors(
+            &self.colors[before],
+            &self.colors[left],
+            &self.colors[right],
+            &self.colors[after],
+            amount,
+            &mut self.rng
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    //no shift so the same position always gets the same color
+    fn still(colors: Vec<Color>, interpolation: Interpolation) -> Colorer
+    {
+        let settings = ColorerSettings{
+            shift: false,
+            interpolation: Arc::new(interpolation),
+            ..Default::default()
+            };
+
+        Colorer::new(colors, settings)
+    }
+
+    fn grays(values: &[u8]) -> Vec<Color>
+    {
+        values.iter().map(|value| Color::new(*value, *value, *value)).collect()
+    }
+
+    #[test]
+    fn cubic_differs_from_linear()
+    {
+        let colors = grays(&[0, 100, 255]);
+
+        let mut cubic = still(colors.clone(), Interpolation::Cubic);
+        let mut linear = still(colors, Interpolation::Linear);
+
+        //the stops on either side pull the curve away from a straight line
+        for position in [0.25, 0.75]
+        {
+            assert_ne!(cubic.color_at(position), linear.color_at(position));
+        }
+
+        assert_eq!(cubic.color_at(0.0), Color::new(0, 0, 0));
+    }
+
+    #[test]
+    fn hsv_goes_through_yellow()
+    {
+        let red = Color::new(255, 0, 0);
+        let green = Color::new(0, 255, 0);
+
+        assert_eq!(red.interpolate(&green, 0.5, &Interpolation::Hsv), Color::new(255, 255, 0));
+    }
+
+    #[test]
+    fn hsv_takes_the_short_way_around()
+    {
+        let lhs = Color::from_hsv(350.0, 1.0, 1.0);
+        let rhs = Color::from_hsv(10.0, 1.0, 1.0);
+
+        assert_eq!(lhs.interpolate(&rhs, 0.5, &Interpolation::Hsv), Color::new(255, 0, 0));
+    }
+
+    #[test]
+    fn repeat_goes_through_the_gradient_twice()
+    {
+        let colors = vec![Color::new(255, 0, 0), Color::new(0, 255, 0), Color::new(0, 0, 255)];
+
+        let mut once = still(colors.clone(), Interpolation::Linear);
+        let mut twice = Colorer::new(colors, ColorerSettings{shift: false, repeat: 2.0, ..Default::default()});
+
+        //the first half of the text already covers the whole gradient
+        for position in [0.0, 0.1, 0.2, 0.3, 0.4]
+        {
+            assert_eq!(twice.color_at(position), once.color_at(position * 2.0));
+        }
+    }
+
+    #[test]
+    fn commands_are_left_alone()
+    {
+        let mut colorer = still(vec![Color::new(255, 0, 0)], Interpolation::Linear);
+
+        assert_eq!(colorer.color_text("/warp home"), "/warp home");
+        assert_eq!(colorer.color_text("  /warp home"), "  /warp home");
+
+        let settings = ColorerSettings{shift: false, color_commands: true, ..Default::default()};
+        let mut colorer = Colorer::new(vec![Color::new(255, 0, 0)], settings);
+
+        assert_eq!(colorer.color_text("/warp home"), "[c/ff0000:/warp home]");
+    }
+
+    #[test]
+    fn linear_gamma_midpoint_is_lighter()
+    {
+        let black = Color::new(0, 0, 0);
+        let white = Color::new(255, 255, 255);
+
+        let gamma = Interpolation::LinearGamma(Interpolation::DEFAULT_GAMMA);
+        let middle = black.interpolate(&white, 0.5, &gamma);
+
+        assert!((180..=190).contains(&middle.r), "{}", middle.r);
+        assert_eq!(black.interpolate(&white, 0.5, &Interpolation::Linear).r, 128);
+    }
+
+    #[test]
+    fn item_tags_dont_break_the_gradient()
+    {
+        let colors = vec![Color::new(255, 0, 0), Color::new(0, 0, 255)];
+
+        let with_tag = still(colors.clone(), Interpolation::Linear).color_text("hello [i:29] world");
+        let without_tag = still(colors, Interpolation::Linear).color_text("hello  world");
+
+        assert!(with_tag.contains(" [i:29] "));
+        assert_eq!(with_tag.replace("[i:29]", ""), without_tag);
+    }
+
+    #[test]
+    fn same_seed_same_colors()
+    {
+        let seeded = ||
+        {
+            let settings = ColorerSettings{
+                seed: Some(7),
+                jitter: 40,
+                interpolation: Arc::new(Interpolation::Random),
+                ..Default::default()
+                };
+
+            Colorer::new(grays(&[0, 128, 255]), settings)
+        };
+
+        let (mut lhs, mut rhs) = (seeded(), seeded());
+
+        for message in ["hello", "a longer message", "hello"]
+        {
+            assert_eq!(lhs.color_text(message), rhs.color_text(message));
+        }
+    }
+
+    #[test]
+    fn empty_and_tiny_messages()
+    {
+        let mut colorer = still(vec![Color::new(255, 0, 0), Color::new(0, 0, 255)], Interpolation::Linear);
+
+        assert_eq!(colorer.color_text(""), "");
+        assert_eq!(colorer.color_text(" "), " ");
+        assert_eq!(colorer.color_text("a"), "[c/ff0000:a]");
+
+        assert_eq!(colorer.color_chars("a"), vec![('a', Some(Color::new(255, 0, 0)))]);
+    }
+
+    #[test]
+    fn brackets_are_escaped()
+    {
+        let mut colorer = still(vec![Color::new(255, 0, 0)], Interpolation::Linear);
+
+        assert_eq!(colorer.color_text("a]b[c"), "[c/ff0000:a\\]b\\[c]");
+    }
+
+    #[test]
+    fn reverse_starts_from_the_last_color()
+    {
+        let settings = ColorerSettings{shift: false, reverse: true, ..Default::default()};
+        let mut colorer = Colorer::new(vec![Color::new(255, 0, 0), Color::new(0, 0, 255)], settings);
+
+        assert!(colorer.color_text("ab").starts_with("[c/0000ff:a]"));
+    }
+
+    #[test]
+    fn oklch_keeps_red_to_blue_saturated()
+    {
+        let red = Color::new(255, 0, 0);
+        let blue = Color::new(0, 0, 255);
+
+        let chroma = |color: Color|
+        {
+            let [_, a, b] = color.to_oklab();
+
+            a.hypot(b)
+        };
+
+        let lowest = chroma(red).min(chroma(blue)) * 0.9;
+
+        for step in 0..=10
+        {
+            let amount = step as f32 / 10.0;
+
+            let color = red.interpolate(&blue, amount, &Interpolation::Oklch);
+            assert!(chroma(color)>=lowest, "{color:?} at {amount}");
+        }
+
+        //a plain rgb blend goes through a dull purple in the middle
+        assert!(chroma(red.interpolate(&blue, 0.5, &Interpolation::Linear))<lowest);
+    }
+
+    #[test]
+    fn smoothstep_is_symmetric()
+    {
+        let black = Color::new(0, 0, 0);
+        let white = Color::new(255, 255, 255);
+
+        let smooth = |amount| black.interpolate(&white, amount, &Interpolation::Smoothstep).r;
+
+        assert_eq!(smooth(0.0), 0);
+        assert_eq!(smooth(0.5), 128);
+        assert_eq!(smooth(1.0), 255);
+
+        for amount in [0.1, 0.25, 0.4]
+        {
+            assert_eq!(smooth(amount) as u32 + smooth(1.0 - amount) as u32, 255);
+            assert!(smooth(amount)<black.interpolate(&white, amount, &Interpolation::Linear).r);
+        }
+    }
+
+    //what the game would show, every character with the color of the tag its in
+    fn rendered(text: &str) -> Vec<(char, Option<Color>)>
+    {
+        let mut chars = Vec::new();
+
+        let mut rest = text;
+        while !rest.is_empty()
+        {
+            if let Some(tag) = rest.strip_prefix("[c/")
+            {
+                let channel = |index: usize| u8::from_str_radix(&tag[index..index + 2], 16).unwrap();
+                let color = Color::new(channel(0), channel(2), channel(4));
+
+                //brackets typed by the player are escaped inside the tag
+                let (end, _) = tag.match_indices(']').find(|(index, _)| !tag[..*index].ends_with('\\')).unwrap();
+                let inner = tag[7..end].replace("\\[", "[").replace("\\]", "]");
+
+                chars.extend(inner.chars().map(|c| (c, Some(color))));
+
+                rest = &tag[end + 1..];
+            } else
+            {
+                let c = rest.chars().next().unwrap();
+                chars.push((c, None));
+
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+
+        chars
+    }
+
+    #[test]
+    fn runs_look_the_same_as_single_letters()
+    {
+        let colors = vec![Color::new(255, 0, 0), Color::new(0, 255, 0), Color::new(0, 0, 255)];
+        let message = "hello world again and again";
+
+        //neighbouring letters with the same color share a tag
+        let nearest = still(colors.clone(), Interpolation::Nearest).color_text(message);
+        assert_eq!(nearest.matches("[c/").count(), 3);
+
+        for interpolation in [Interpolation::Nearest, Interpolation::Linear]
+        {
+            let text = still(colors.clone(), interpolation.clone()).color_text(message);
+            let chars = still(colors.clone(), interpolation).color_chars(message);
+
+            let rendered = rendered(&text);
+            assert_eq!(rendered.len(), chars.len());
+
+            for ((c, shown), (expected_c, expected)) in rendered.into_iter().zip(chars)
+            {
+                assert_eq!(c, expected_c);
+
+                if expected.is_some()
+                {
+                    assert_eq!(shown, expected, "{c} in {text}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cubic_keeps_going_the_same_way_across_stops()
+    {
+        let mut colorer = still(grays(&[0, 60, 160, 255]), Interpolation::Cubic);
+
+        let values: Vec<u8> = (0..100).map(|step| colorer.color_at(step as f32 / 100.0).r).collect();
+
+        //the stops at a third and two thirds dont make the gradient turn back
+        assert!(values.windows(2).all(|pair| pair[0]<=pair[1]), "{values:?}");
+        assert_eq!(values[0], 0);
+        assert!(values[99]>250);
+    }
+
+    //every bracket that isnt escaped gets closed and tags are never nested
+    fn balanced(text: &str) -> bool
+    {
+        let mut depth = 0;
+
+        let mut chars = text.chars();
+        while let Some(c) = chars.next()
+        {
+            match c
+            {
+                '\\' => { chars.next(); },
+                '[' => depth += 1,
+                ']' => depth -= 1,
+                _ => ()
+            }
+
+            if !(0..=1).contains(&depth)
+            {
+                return false;
+            }
+        }
+
+        depth==0
+    }
+
+    #[test]
+    fn tags_around_plain_text()
+    {
+        let colors = vec![Color::new(255, 0, 0), Color::new(0, 0, 255)];
+
+        for message in ["[hello", "[ hi", "a [i:1] b", "hi [i:1]", "a]", "[i:1][g:2]x", "x[i:1][g:2]", "[i:1]"]
+        {
+            let text = still(colors.clone(), Interpolation::Linear).color_text(message);
+
+            assert!(balanced(&text), "{text}");
+
+            let rendered = rendered(&text);
+            assert_eq!(rendered.iter().map(|(c, _)| c).collect::<String>(), message, "{text}");
+
+            //everything outside of item tags gets a color, and only one
+            let in_tag = Colorer::tag_mask(message);
+            for ((c, color), in_tag) in rendered.into_iter().zip(in_tag)
+            {
+                assert_eq!(color.is_some(), !in_tag && c!=' ', "{c} in {text}");
+            }
+        }
+
+        //a single color goes through the solid path which wraps whole runs of text at once
+        let expected = [
+            ("[hello", "[c/ff0000:\\[hello]"),
+            ("[ hi", "[c/ff0000:\\[ hi]"),
+            ("a [i:1] b", "[c/ff0000:a] [i:1] [c/ff0000:b]"),
+            ("hi [i:1]", "[c/ff0000:hi] [i:1]"),
+            ("a]", "[c/ff0000:a\\]]"),
+            ("[i:1][g:2]x", "[i:1][g:2][c/ff0000:x]"),
+            ("x[i:1][g:2]", "[c/ff0000:x][i:1][g:2]"),
+            ("[i:1]", "[i:1]"),
+            ("a[i:1]b[g:2]c", "[c/ff0000:a][i:1][c/ff0000:b][g:2][c/ff0000:c]")
+            ];
+
+        for (message, expected) in expected
+        {
+            let text = still(vec![Color::new(255, 0, 0)], Interpolation::Linear).color_text(message);
+
+            assert_eq!(text, expected);
+            assert!(balanced(&text), "{text}");
+
+            //every run of text between item tags gets exactly one tag
+            let mut runs = Vec::new();
+            let mut run = String::new();
+            for (c, in_tag) in message.chars().zip(Colorer::tag_mask(message))
+            {
+                if in_tag
+                {
+                    runs.push(std::mem::take(&mut run));
+                } else
+                {
+                    run.push(c);
+                }
+            }
+
+            runs.push(run);
+
+            let runs = runs.iter().filter(|run| !run.trim().is_empty()).count();
+            assert_eq!(text.matches("[c/").count(), runs, "{text}");
+        }
+    }
+
+    #[test]
+    fn gamma_moves_the_midpoint()
+    {
+        let black = Color::new(0, 0, 0);
+        let white = Color::new(255, 255, 255);
+
+        let middle = |gamma| black.interpolate(&white, 0.5, &Interpolation::LinearGamma(gamma)).r;
+
+        assert_eq!(middle(1.0), 128);
+
+        let middles: Vec<u8> = [1.0, 1.8, 2.2, 2.4].into_iter().map(middle).collect();
+        assert!(middles.windows(2).all(|pair| pair[0]<pair[1]), "{middles:?}");
+    }
+
+    #[test]
+    fn colors_round_trip()
+    {
+        for color in [Color::new(0, 0, 0), Color::new(255, 128, 7), Color::new(18, 52, 86)]
+        {
+            assert_eq!(format!("#{color}").parse::<Color>(), Ok(color));
+
+            let rgb = format!("{}, {}, {}", color.r, color.g, color.b);
+            assert_eq!(rgb.parse::<Color>(), Ok(color));
+        }
+
+        assert_ne!(Color::new(1, 2, 3), Color::with_alpha(1, 2, 3, 4));
+
+        assert_eq!(Interpolation::try_from("bias:0.3"), Ok(Interpolation::Bias(0.3)));
+    }
+
+    #[test]
+    fn mirror_has_no_seam()
+    {
+        let colors = vec![Color::new(255, 0, 0), Color::new(0, 255, 0), Color::new(0, 0, 255)];
+
+        let repeated = |repeat_mode|
+        {
+            let settings = ColorerSettings{shift: false, repeat: 2.0, repeat_mode, ..Default::default()};
+
+            Colorer::new(colors.clone(), settings)
+        };
+
+        let mut wrap = repeated(RepeatMode::Wrap);
+        let mut mirror = repeated(RepeatMode::Mirror);
+
+        //the first repeat ends at the middle
+        let (before, after) = (0.49, 0.51);
+
+        assert_eq!(mirror.color_at(before), mirror.color_at(after));
+        assert_eq!(wrap.color_at(0.5), Color::new(255, 0, 0));
+        assert_eq!(wrap.color_at(after), Color::new(245, 10, 0));
+        assert!(wrap.color_at(before).b>200);
+
+        assert_eq!(wrap.color_at(0.2), mirror.color_at(0.2));
+    }
+
+    #[test]
+    fn unfinished_tag_at_the_end()
+    {
+        let mut colorer = still(vec![Color::new(255, 0, 0), Color::new(0, 0, 255)], Interpolation::Linear);
+
+        let text = colorer.color_text("hello [i:");
+        assert!(balanced(&text), "{text}");
+
+        //the dangling bracket is just text so it gets colored like the rest
+        let rendered = rendered(&text);
+        assert_eq!(rendered.iter().map(|(c, _)| c).collect::<String>(), "hello [i:");
+        assert!(rendered.iter().all(|(c, color)| *c==' ' || color.is_some()), "{text}");
+    }
+
+    #[test]
+    fn hue_shift_turns_red_green()
+    {
+        let shifted = |hue_shift|
+        {
+            let settings = ColorerSettings{shift: false, hue_shift, ..Default::default()};
+
+            Colorer::new(vec![Color::new(255, 0, 0)], settings).color_at(0.0)
+        };
+
+        assert_eq!(shifted(120.0), Color::new(0, 255, 0));
+
+        //out of range degrees go around the circle
+        assert_eq!(shifted(-240.0), Color::new(0, 255, 0));
+        assert_eq!(shifted(480.0), Color::new(0, 255, 0));
+    }
+
+    #[test]
+    fn color_chars_follow_the_gradient()
+    {
+        let mut colorer = still(grays(&[0, 255]), Interpolation::Linear);
+
+        let chars = colorer.color_chars("ab [i:1] cd");
+        assert_eq!(chars.len(), "ab [i:1] cd".chars().count());
+
+        let colored: Vec<u8> = chars.iter().filter_map(|(_, color)| color.map(|color| color.r)).collect();
+
+        //the spaces and the item tag dont get a color
+        assert_eq!(colored.len(), 4);
+        assert!(colored.windows(2).all(|pair| pair[0]<pair[1]), "{colored:?}");
+    }
+
+    #[test]
+    fn shift_stays_within_the_amount()
+    {
+        let settings = ColorerSettings{shift_amount: 0.2, seed: Some(11), ..Default::default()};
+        let mut colorer = Colorer::new(grays(&[0, 255]), settings);
+
+        let mut shifts = Vec::new();
+        for _ in 0..200
+        {
+            colorer.word();
+            shifts.push(colorer.shift.unwrap());
+        }
+
+        assert!(shifts.iter().all(|shift| (0.0..=0.2).contains(shift)), "{shifts:?}");
+
+        //and it does actually move around
+        assert!(shifts.iter().any(|shift| *shift>0.1));
+    }
+
+    #[test]
+    fn write_tag_matches_format()
+    {
+        for color in [Color::new(0, 0, 0), Color::new(255, 255, 255), Color::new(1, 171, 16)]
+        {
+            for c in ['a', ' ', 'é', '🌈']
+            {
+                let mut out = String::new();
+                color.write_tag(&mut out, c);
+
+                assert_eq!(out, format!("[c/{color}:{c}]"));
+            }
+        }
+    }
+
+    #[test]
+    fn three_steps_three_colors()
+    {
+        let settings = ColorerSettings{shift: false, steps: Some(3), ..Default::default()};
+        let mut colorer = Colorer::new(grays(&[0, 255]), settings);
+
+        let chars = colorer.color_chars(&"a".repeat(60));
+
+        let mut colors: Vec<Color> = chars.into_iter().filter_map(|(_, color)| color).collect();
+        colors.dedup();
+
+        assert_eq!(colors.len(), 3, "{colors:?}");
+
+        //and every band is one tag
+        assert_eq!(colorer.color_text(&"a".repeat(60)).matches("[c/").count(), 3);
+    }
+
+    #[test]
+    fn color_at_the_ends()
+    {
+        let colors = grays(&[0, 60, 120, 180]);
+
+        let mut colorer = still(colors.clone(), Interpolation::Linear);
+
+        assert_eq!(colorer.color_at(0.0), colors[0]);
+        assert_eq!(colorer.color_at(0.5), Color::new(90, 90, 90));
+
+        //the end of the last repeat is back at the start
+        assert_eq!(colorer.color_at(1.0), colors[0]);
+        assert!(colorer.color_at(0.999).r>=179);
+
+        let mut mirrored = Colorer::new(colors.clone(), ColorerSettings{
+            shift: false,
+            repeat: 2.0,
+            repeat_mode: RepeatMode::Mirror,
+            ..Default::default()
+            });
+
+        assert_eq!(mirrored.color_at(0.0), colors[0]);
+        assert_eq!(mirrored.color_at(0.5), colors[3]);
+        assert_eq!(mirrored.color_at(1.0), colors[0]);
+
+        //a shifted gradient wraps around so a quarter lands on the second color
+        colorer.shift = Some(0.25);
+
+        assert_eq!(colorer.color_at(0.0), colors[1]);
+        assert_eq!(colorer.color_at(0.75), colors[0]);
+        assert_eq!(colorer.color_at(1.0), colors[1]);
+    }
+
+    #[test]
+    fn hsv_stops()
+    {
+        assert_eq!("hsv(0,100,100)".parse::<Color>(), Ok(Color::new(255, 0, 0)));
+        assert_eq!("HSV( 120, 100, 100 )".parse::<Color>(), Ok(Color::new(0, 255, 0)));
+        assert_eq!("hsv(360,0,100)".parse::<Color>(), Ok(Color::new(255, 255, 255)));
+
+        for bad in ["hsv(361,100,100)", "hsv(0,101,0)", "hsv(0,0,-1)", "hsv(0,100)", "hsv(0,100,100"]
+        {
+            assert!(bad.parse::<Color>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn shift_carries_on_without_newline_reset()
+    {
+        let seeded = |newline_reset|
+        {
+            let settings = ColorerSettings{seed: Some(3), newline_reset, ..Default::default()};
+
+            Colorer::new(grays(&[0, 128, 255]), settings)
+        };
+
+        let mut continuous = seeded(false);
+        let shift = continuous.shift;
+
+        let first = continuous.color_text("hello there");
+        for message in ["a different message", "hello there"]
+        {
+            continuous.color_text(message);
+            assert_eq!(continuous.shift, shift);
+        }
+
+        assert_eq!(continuous.color_text("hello there"), first);
+
+        let chars = continuous.color_chars("hello there");
+        assert_eq!(continuous.shift, shift);
+        assert_eq!(continuous.color_chars("hello there"), chars);
+        assert_eq!(continuous.color_text("hello there"), first);
+
+        //same seed but rerolled for every message
+        let mut reset = seeded(true);
+
+        let rerolled = reset.color_text("hello there");
+        assert_ne!(reset.shift, shift);
+        assert_ne!(reset.color_text("hello there"), rerolled);
+
+        let chars = reset.color_chars("hello there");
+        assert_ne!(reset.color_chars("hello there"), chars);
+    }
+
+    #[test]
+    fn line_breaks_stay_out_of_tags()
+    {
+        let colors = vec![Color::new(255, 0, 0), Color::new(0, 0, 255)];
+        let messages = ["first line\r\nsecond line", "a\nb\nc", "\r\nstarts broken", "tab\there\x07", "end\r\n"];
+
+        for color_spaces in [false, true]
+        {
+            let settings = ColorerSettings{shift: false, color_spaces, ..Default::default()};
+
+            for message in messages
+            {
+                let text = Colorer::new(colors.clone(), settings.clone()).color_text(message);
+                assert!(balanced(&text), "{text:?}");
+
+                let rendered = rendered(&text);
+                assert_eq!(rendered.iter().map(|(c, _)| *c).collect::<String>(), message);
+
+                for (c, color) in rendered
+                {
+                    assert!(!c.is_control() || color.is_none(), "{c:?} in {text:?}");
+                }
+            }
+        }
+
+        //a windows line break turns into a single space
+        let settings = ColorerSettings{shift: false, strip_control: true, ..Default::default()};
+        let mut stripping = Colorer::new(colors, settings);
+
+        for (message, expected) in [("first\r\nsecond", "first second"), ("a\nb\x07", "a b"), ("end\r\n", "end ")]
+        {
+            let text = stripping.color_text(message);
+
+            assert!(!text.chars().any(char::is_control), "{text:?}");
+            assert_eq!(rendered(&text).into_iter().map(|(c, _)| c).collect::<String>(), expected);
+        }
+    }
+
+    #[test]
+    fn quantized_colors_are_in_the_palette()
+    {
+        let palette = vec![Color::new(0, 0, 0), Color::new(255, 255, 255), Color::new(255, 0, 0), Color::new(0, 0, 255)];
+
+        assert_eq!(Color::new(250, 30, 20).nearest(&palette), Some(&palette[2]));
+        assert_eq!(Color::new(20, 20, 20).nearest(&[]), None);
+
+        let colors = vec![Color::new(255, 128, 0), Color::new(0, 200, 100), Color::new(120, 0, 255)];
+
+        for interpolation in [Interpolation::Linear, Interpolation::Cubic, Interpolation::Hsv, Interpolation::Random]
+        {
+            let settings = ColorerSettings{
+                seed: Some(5),
+                jitter: 30,
+                interpolation: Arc::new(interpolation),
+                palette: Some(palette.clone()),
+                ..Default::default()
+                };
+
+            let mut colorer = Colorer::new(colors.clone(), settings);
+
+            for message in ["hello there", "a much longer message going through every color", "x"]
+            {
+                let text = colorer.color_text(message);
+
+                for (c, color) in rendered(&text)
+                {
+                    assert!(color.is_none_or(|color| palette.contains(&color)), "{c} in {text}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn names_are_css_colors()
+    {
+        for (name, expected) in [("green", (0, 128, 0)), ("lime", (0, 255, 0)), ("Gray", (128, 128, 128)), ("purple", (128, 0, 128))]
+        {
+            assert_eq!(name.parse::<Color>(), Ok(Color::from(expected)), "{name}");
+        }
+
+        assert_eq!(Color::from_name("limegreen"), None);
+    }
+
+    #[test]
+    fn color_chars_matches_what_gets_sent()
+    {
+        let colors = vec![Color::new(255, 0, 0), Color::new(0, 0, 255)];
+
+        let settings = [
+            ColorerSettings{shift: false, ..Default::default()},
+            ColorerSettings{shift: false, strip_control: true, ..Default::default()},
+            ColorerSettings{shift: false, accent: Some(3), ..Default::default()}
+            ];
+
+        for settings in settings
+        {
+            for message in ["/w someone hi", "first\r\nsecond", "hello there friend", "  "]
+            {
+                let text = Colorer::new(colors.clone(), settings.clone()).color_text(message);
+                let chars = Colorer::new(colors.clone(), settings.clone()).color_chars(message);
+
+                assert_eq!(rendered(&text), chars, "{text:?}");
+            }
         }
 
-        self.colors[left].interpolate(&self.colors[right], amount, &self.interpolation)
+        let mut colorer = Colorer::new(colors.clone(), ColorerSettings{shift: false, ..Default::default()});
+        assert!(colorer.color_chars("/w someone hi").iter().all(|(_, color)| color.is_none()));
+
+        let mut accented = Colorer::new(colors, ColorerSettings{shift: false, accent: Some(3), ..Default::default()});
+
+        let chars = accented.color_chars("hello there");
+        assert!(chars[..5].iter().all(|(_, color)| color.is_some()));
+        assert!(chars[5..].iter().all(|(_, color)| color.is_none()));
+    }
+
+    #[test]
+    #[should_panic(expected = "steps need to be at least 2")]
+    fn one_step_is_rejected()
+    {
+        Colorer::new(grays(&[0, 255]), ColorerSettings{steps: Some(1), ..Default::default()});
+    }
+
+    #[test]
+    #[should_panic(expected = "steps need to be at least 2")]
+    fn zero_steps_are_rejected()
+    {
+        Colorer::new(grays(&[0, 255]), ColorerSettings{steps: Some(0), ..Default::default()});
     }
 }
@@ -0,0 +1,75 @@
+use std::fs::File;
+
+use std::sync::Mutex;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use std::io::{Write, BufWriter};
+
+use crate::log::log_error;
+
+
+#[derive(Clone, Copy)]
+pub enum Direction
+{
+    Client,
+    Server
+}
+
+impl Direction
+{
+    fn name(&self) -> &'static str
+    {
+        match self
+        {
+            Direction::Client => "client",
+            Direction::Server => "server"
+        }
+    }
+}
+
+//every record is a line with who sent it, unix time in milliseconds and the byte count
+//like "client 1700000000000 12", then exactly that many raw bytes and a newline
+pub struct PacketDump
+{
+    file: Mutex<BufWriter<File>>
+}
+
+impl PacketDump
+{
+    pub fn create(path: &str) -> Result<Self, String>
+    {
+        let file = File::create(path)
+            .map_err(|err| format!("could not create dump file {path}: {err}"))?;
+
+        Ok(PacketDump{file: Mutex::new(BufWriter::new(file))})
+    }
+
+    pub fn record(&self, direction: Direction, data: &[u8])
+    {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_millis());
+
+        //a thread that panicked while writing doesnt make the file unusable
+        let mut file = self.file.lock().unwrap_or_else(|err| err.into_inner());
+
+        let written = writeln!(file, "{} {time} {}", direction.name(), data.len())
+            .and_then(|_| file.write_all(data))
+            .and_then(|_| file.write_all(b"\n"));
+
+        if let Err(err) = written
+        {
+            log_error!("error writing to the dump file: {err}");
+        }
+    }
+
+    pub fn flush(&self)
+    {
+        let mut file = self.file.lock().unwrap_or_else(|err| err.into_inner());
+
+        if let Err(err) = file.flush()
+        {
+            log_error!("error writing to the dump file: {err}");
+        }
+    }
+}
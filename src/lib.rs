@@ -0,0 +1,13 @@
+pub use colorer::{Colorer, ColorerSettings, Color, Interpolation, Interpolator, Easing, RepeatMode};
+pub use proxy::{ProxyConfig, MAX_MESSAGE_LENGTH, run_proxy, serve_proxy, shutdown};
+pub use log::{LogLevel, set_log_level};
+
+mod log;
+
+mod colorer;
+mod packet;
+mod dump;
+mod socks5;
+mod resolve;
+mod stats;
+mod proxy;
@@ -0,0 +1,57 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel
+{
+    Error,
+    Info,
+    Debug
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+
+pub fn set_log_level(level: LogLevel)
+{
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: LogLevel) -> bool
+{
+    level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+macro_rules! log_error
+{
+    ($($arg:tt)*) =>
+    {
+        if $crate::log::enabled($crate::log::LogLevel::Error)
+        {
+            eprintln!($($arg)*);
+        }
+    }
+}
+
+macro_rules! log_info
+{
+    ($($arg:tt)*) =>
+    {
+        if $crate::log::enabled($crate::log::LogLevel::Info)
+        {
+            println!($($arg)*);
+        }
+    }
+}
+
+macro_rules! log_debug
+{
+    ($($arg:tt)*) =>
+    {
+        if $crate::log::enabled($crate::log::LogLevel::Debug)
+        {
+            println!($($arg)*);
+        }
+    }
+}
+
+pub(crate) use {log_error, log_info, log_debug};
@@ -1,415 +1,1830 @@
 use std::env;
 
-use std::process;
+use std::fs;
 
-use std::thread;
+use std::io;
 
-use std::io::{Write, BufReader, BufRead, ErrorKind};
-use std::net::{TcpStream, TcpListener};
+use std::fmt::Write;
 
-use colorer::{Colorer, Color, Interpolation};
+use std::collections::HashMap;
 
-mod colorer;
+use std::process::ExitCode;
+
+use std::time::Duration;
+
+use std::sync::Arc;
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use std::num::NonZeroU16;
+
+use serde::Deserialize;
+
+use terraria_cool_colorer::{ProxyConfig, MAX_MESSAGE_LENGTH, Colorer, ColorerSettings, Color, Interpolation, Easing, RepeatMode, LogLevel};
 
 
 struct ColorParser
 {
-    colors: Vec<String>
+    colors: Vec<String>,
+    clamp: bool
+}
+
+impl ColorParser
+{
+    pub fn new(colors: String, clamp: bool) -> Self
+    {
+        let colors = colors.split([',', ';']).map(|s| s.trim().to_string()).collect();
+        ColorParser{colors, clamp}
+    }
+
+    //colors with their position in the gradient if it was given like red@0.2
+    pub fn parse(&self, warnings: &mut Vec<String>) -> Result<Vec<(Color, Option<f32>)>, String>
+    {
+        let mut colors = self.colors.iter().map(|s| s.as_str()).peekable();
+
+        let mut parsed = Vec::new();
+        while let Some(r) = colors.next()
+        {
+            let index = parsed.len() + 1;
+
+            if r.is_empty()
+            {
+                //allow a separator at the very end
+                if colors.peek().is_none()
+                {
+                    break;
+                }
+
+                return Err(format!("color {index} has an empty value (doubled separator?)"));
+            }
+
+            //the commas inside hsv(h,s,v) split it up like a triplet
+            if r.to_lowercase().starts_with("hsv(")
+            {
+                let mut values = vec![r];
+                for value in ["saturation", "value"]
+                {
+                    let next = colors.next()
+                        .ok_or_else(|| format!("color {index} ({}) has no {value}", values.join(",")))?;
+
+                    values.push(next);
+                }
+
+                let joined = values.join(",");
+                let (hsv, position) = Self::split_position(&joined)
+                    .map_err(|err| format!("color {index}: {err}"))?;
+
+                parsed.push((hsv.parse().map_err(|err| format!("color {index}: {err}"))?, position));
+                continue;
+            }
+
+            //hex colors and names are a single token, only triplets span multiple
+            if r.starts_with('#') || r.starts_with(|c: char| c.is_alphabetic())
+            {
+                let (r, position) = Self::split_position(r)
+                    .map_err(|err| format!("color {index}: {err}"))?;
+
+                parsed.push((r.parse().map_err(|err| format!("color {index}: {err}"))?, position));
+                continue;
+            }
+
+            let mut channels = vec![r];
+            for channel in ["green", "blue"]
+            {
+                match colors.next()
+                {
+                    Some("") =>
+                    {
+                        return Err(format!(
+                            "color {index} ({}) has an empty {channel} value (doubled separator?)",
+                            channels.join(", ")
+                            ));
+                    },
+                    Some(value) => channels.push(value),
+                    None =>
+                    {
+                        return Err(format!(
+                            "color {index} ({}) has no {channel} value, colors need 3 values",
+                            channels.join(", ")
+                            ));
+                    }
+                }
+            }
+
+            //the position is after the last channel
+            let (blue, position) = Self::split_position(channels[2])
+                .map_err(|err| format!("color {index} ({}): {err}", channels.join(", ")))?;
+
+            let channels = [channels[0], channels[1], blue];
+
+            let color = match channels.join(",").parse()
+            {
+                Err(err) if self.clamp =>
+                {
+                    //out of range values get clamped but anything that isnt a number still fails
+                    let clamped = Color::from_clamped(channels);
+                    if clamped.is_ok()
+                    {
+                        warnings.push(format!("color {index} ({}): {err}, clamping it", channels.join(", ")));
+                    }
+
+                    clamped
+                },
+                result => result
+            }.map_err(|err| format!("color {index} ({}): {err}", channels.join(", ")))?;
+
+            parsed.push((color, position));
+        }
+
+        Ok(parsed)
+    }
+
+    pub fn parse_stop(&self, warnings: &mut Vec<String>) -> Result<(Color, Option<f32>), String>
+    {
+        let parsed = self.parse(warnings)?;
+
+        match parsed.as_slice()
+        {
+            [stop] => Ok(*stop),
+            _ => Err(format!("expected one color, found {}", parsed.len()))
+        }
+    }
+
+    pub fn parse_single(&self, warnings: &mut Vec<String>) -> Result<Color, String>
+    {
+        match self.parse_stop(warnings)?
+        {
+            (color, None) => Ok(color),
+            (_, Some(_)) => Err("a single color cant have a position".to_string())
+        }
+    }
+
+    fn split_position(token: &str) -> Result<(&str, Option<f32>), String>
+    {
+        let Some((value, position)) = token.split_once('@')
+        else
+        {
+            return Ok((token, None));
+        };
+
+        let position: f32 = position.trim().parse()
+            .map_err(|err| format!("{err} cannot be converted to position"))?;
+
+        if !(0.0..=1.0).contains(&position)
+        {
+            return Err(format!("position {position} is outside of 0 to 1"));
+        }
+
+        Ok((value.trim(), Some(position)))
+    }
+}
+
+fn load_colors_file(path: &str, clamp: bool, warnings: &mut Vec<String>) -> Result<Vec<(Color, Option<f32>)>, String>
+{
+    let text = fs::read_to_string(path)
+        .map_err(|err| format!("could not read {path}: {err}"))?;
+
+    let mut colors = Vec::new();
+    for (index, line) in text.lines().enumerate()
+    {
+        let line = line.trim();
+
+        //a # right before hex digits is a color, so comments need a space after it
+        let comment = line.strip_prefix('#')
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace));
+
+        if line.is_empty() || comment
+        {
+            continue;
+        }
+
+        let line_number = index + 1;
+
+        let stop = ColorParser::new(line.to_string(), clamp).parse_stop(warnings)
+            .map_err(|err| format!("{path} line {line_number}: {err}"))?;
+
+        colors.push(stop);
+    }
+
+    if colors.is_empty()
+    {
+        return Err(format!("{path} has no colors"));
+    }
+
+    Ok(colors)
+}
+
+//samples evenly spaced pixels across the top row, the first and last pixels are always included
+fn load_gradient_image(path: &str, stops: usize) -> Result<Vec<(Color, Option<f32>)>, String>
+{
+    let file = fs::File::open(path)
+        .map_err(|err| format!("could not read {path}: {err}"))?;
+
+    let mut decoder = png::Decoder::new(file);
+
+    //palettes and small bit depths get expanded and 16 bit channels cut down to 8
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+
+    let mut reader = decoder.read_info()
+        .map_err(|err| format!("could not decode {path}: {err}"))?;
+
+    let info = reader.info();
+    if info.width==0
+    {
+        return Err(format!("{path} has no pixels"));
+    }
+
+    let width = info.width as usize;
+
+    let (color_type, _) = reader.output_color_type();
+
+    //interlaced images only have every row in full once the whole frame is decoded
+    let mut frame = vec![0; reader.output_buffer_size()];
+    let output = reader.next_frame(&mut frame)
+        .map_err(|err| format!("could not decode {path}: {err}"))?;
+
+    let row = &frame[..output.line_size];
+
+    let pixel = |x: usize| -> Color
+    {
+        let channels = color_type.samples();
+        let pixel = &row[x * channels..(x + 1) * channels];
+
+        match color_type
+        {
+            png::ColorType::Grayscale => Color::new(pixel[0], pixel[0], pixel[0]),
+            png::ColorType::GrayscaleAlpha => Color::with_alpha(pixel[0], pixel[0], pixel[0], pixel[1]),
+            png::ColorType::Rgba => Color::with_alpha(pixel[0], pixel[1], pixel[2], pixel[3]),
+            //indexed images are already expanded to rgb
+            png::ColorType::Rgb | png::ColorType::Indexed => Color::new(pixel[0], pixel[1], pixel[2])
+        }
+    };
+
+    let colors = (0..stops).map(|index|
+    {
+        let x = index * (width - 1) / (stops - 1);
+
+        (pixel(x), None)
+    }).collect();
+
+    Ok(colors)
+}
+
+//every option that can be saved in a config file, the command line fills in the same struct
+//and whatever it has wins over the file, checking the values happens once theyre merged
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct Options
+{
+    connect_address: Option<Vec<String>>,
+    server_port: Option<NonZeroU16>,
+    socks5: Option<String>,
+    listen_address: Option<IpAddr>,
+    allow_remote_clients: Option<bool>,
+    colors: Option<Vec<String>>,
+    clamp: Option<bool>,
+    colors_file: Option<String>,
+    gradient_image: Option<String>,
+    solid: Option<String>,
+    preset: Option<String>,
+    preset_file: Option<String>,
+    rainbow: Option<bool>,
+    stops: Option<usize>,
+    shift: Option<bool>,
+    shift_amount: Option<f32>,
+    newline_reset: Option<bool>,
+    fade_to: Option<String>,
+    accent: Option<usize>,
+    tag_format: Option<String>,
+    palette_quantize: Option<String>,
+    hue_shift: Option<f32>,
+    jitter: Option<u8>,
+    animate: Option<f32>,
+    interpolation: Option<String>,
+    gamma: Option<f32>,
+    interpolation_steps: Option<u32>,
+    easing: Option<String>,
+    reverse: Option<bool>,
+    port: Option<NonZeroU16>,
+    repeat: Option<f32>,
+    repeat_mode: Option<String>,
+    color_commands: Option<bool>,
+    per_word: Option<bool>,
+    color_spaces: Option<bool>,
+    strip_control: Option<bool>,
+    seed: Option<u64>,
+    color_incoming: Option<bool>,
+    color_system: Option<bool>,
+    color_combat_text: Option<bool>,
+    timeout: Option<f32>,
+    max_length: Option<usize>,
+    dump: Option<String>,
+    echo: Option<bool>,
+    max_connections: Option<usize>,
+    color: Option<bool>,
+    game_version: Option<String>,
+    nodelay: Option<bool>,
+    banner: Option<String>,
+    profile: Option<bool>,
+    once: Option<bool>,
+    verbose: Option<bool>,
+    quiet: Option<bool>
+}
+
+impl Options
+{
+    //unset keys are dropped before anything is read so they can be undone from the command line
+    pub fn load(path: &str, unset: &[String], warnings: &mut Vec<String>) -> Result<Self, String>
+    {
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("could not read {path}: {err}"))?;
+
+        let mut table: toml::Table = toml::from_str(&text)
+            .map_err(|err| format!("could not parse {path}: {err}"))?;
+
+        for key in unset
+        {
+            if table.remove(key).is_none()
+            {
+                warnings.push(format!("--unset {key} does nothing, {path} doesnt have it"));
+            }
+        }
+
+        toml::Value::Table(table).try_into()
+            .map_err(|err| format!("could not parse {path}: {err}"))
+    }
+
+    fn picks_colors(&self) -> bool
+    {
+        self.colors.is_some()
+            || self.colors_file.is_some()
+            || self.gradient_image.is_some()
+            || self.solid.is_some()
+            || self.preset.is_some()
+            || self.rainbow==Some(true)
+    }
+
+    //the colors are taken as a whole so a gradient from the file doesnt clash
+    //with a different way of picking them on the command line
+    pub fn or(self, mut file: Options) -> Options
+    {
+        if self.picks_colors()
+        {
+            file.colors = None;
+            file.colors_file = None;
+            file.gradient_image = None;
+            file.solid = None;
+            file.preset = None;
+            file.rainbow = None;
+        }
+
+        Options{
+            connect_address: self.connect_address.or(file.connect_address),
+            server_port: self.server_port.or(file.server_port),
+            socks5: self.socks5.or(file.socks5),
+            listen_address: self.listen_address.or(file.listen_address),
+            allow_remote_clients: self.allow_remote_clients.or(file.allow_remote_clients),
+            colors: self.colors.or(file.colors),
+            clamp: self.clamp.or(file.clamp),
+            colors_file: self.colors_file.or(file.colors_file),
+            gradient_image: self.gradient_image.or(file.gradient_image),
+            solid: self.solid.or(file.solid),
+            preset: self.preset.or(file.preset),
+            preset_file: self.preset_file.or(file.preset_file),
+            rainbow: self.rainbow.or(file.rainbow),
+            stops: self.stops.or(file.stops),
+            shift: self.shift.or(file.shift),
+            shift_amount: self.shift_amount.or(file.shift_amount),
+            newline_reset: self.newline_reset.or(file.newline_reset),
+            fade_to: self.fade_to.or(file.fade_to),
+            accent: self.accent.or(file.accent),
+            tag_format: self.tag_format.or(file.tag_format),
+            palette_quantize: self.palette_quantize.or(file.palette_quantize),
+            hue_shift: self.hue_shift.or(file.hue_shift),
+            jitter: self.jitter.or(file.jitter),
+            animate: self.animate.or(file.animate),
+            interpolation: self.interpolation.or(file.interpolation),
+            gamma: self.gamma.or(file.gamma),
+            interpolation_steps: self.interpolation_steps.or(file.interpolation_steps),
+            easing: self.easing.or(file.easing),
+            reverse: self.reverse.or(file.reverse),
+            port: self.port.or(file.port),
+            repeat: self.repeat.or(file.repeat),
+            repeat_mode: self.repeat_mode.or(file.repeat_mode),
+            color_commands: self.color_commands.or(file.color_commands),
+            per_word: self.per_word.or(file.per_word),
+            color_spaces: self.color_spaces.or(file.color_spaces),
+            strip_control: self.strip_control.or(file.strip_control),
+            seed: self.seed.or(file.seed),
+            color_incoming: self.color_incoming.or(file.color_incoming),
+            color_system: self.color_system.or(file.color_system),
+            color_combat_text: self.color_combat_text.or(file.color_combat_text),
+            timeout: self.timeout.or(file.timeout),
+            max_length: self.max_length.or(file.max_length),
+            dump: self.dump.or(file.dump),
+            echo: self.echo.or(file.echo),
+            max_connections: self.max_connections.or(file.max_connections),
+            color: self.color.or(file.color),
+            game_version: self.game_version.or(file.game_version),
+            nodelay: self.nodelay.or(file.nodelay),
+            banner: self.banner.or(file.banner),
+            profile: self.profile.or(file.profile),
+            once: self.once.or(file.once),
+            verbose: self.verbose.or(file.verbose),
+            quiet: self.quiet.or(file.quiet)
+            }
+    }
+}
+
+//parsing as a u32 first so 70000 says its out of range instead of just invalid
+fn parse_port(port: &str) -> Result<NonZeroU16, String>
+{
+    let port: u32 = port.parse().map_err(|err| format!("{err} cannot be converted to port"))?;
+
+    u16::try_from(port).ok().and_then(NonZeroU16::new)
+        .ok_or_else(|| format!("port must be between 1 and 65535, got {port}"))
+}
+
+//terraria has no gradient tag in any version, [c/rrggbb:text] tags came in 1.3 and
+//anything before that shows them as plain text, so older versions get no tags at all
+fn supports_color_tags(version: &str) -> Result<bool, String>
+{
+    let numbers = version.split('.').map(|number|
+    {
+        number.parse::<u32>().map_err(|err| format!("{err} cannot be converted to game version in {version}"))
+    }).collect::<Result<Vec<_>, _>>()?;
+
+    match numbers[..]
+    {
+        [major, minor, ..] => Ok((major, minor)>=(1, 3)),
+        [major] => Ok(major>1),
+        [] => unreachable!()
+    }
+}
+
+//terraria servers are on 7777 unless set up otherwise so an address without a port gets the default one
+fn with_server_port(address: &str, port: u16) -> Result<String, String>
+{
+    //bare ipv6 addresses are full of colons but never have a port
+    if address.parse::<Ipv6Addr>().is_ok()
+    {
+        return Ok(format!("[{address}]:{port}"));
+    }
+
+    let (host, address_port) = if let Some(bracketed) = address.strip_prefix('[')
+    {
+        let (host, rest) = bracketed.split_once(']')
+            .ok_or_else(|| format!("{address} is missing the ] after its ipv6 address"))?;
+
+        if host.parse::<Ipv6Addr>().is_err()
+        {
+            return Err(format!("{host} in {address} is not an ipv6 address"));
+        }
+
+        let address_port = match rest.strip_prefix(':')
+        {
+            Some(address_port) => Some(address_port),
+            None if rest.is_empty() => None,
+            None => return Err(format!("{address} has {rest} after the ipv6 address instead of a :port"))
+        };
+
+        (format!("[{host}]"), address_port)
+    } else
+    {
+        let (host, address_port) = match address.split_once(':')
+        {
+            Some((host, address_port)) => (host, Some(address_port)),
+            None => (address, None)
+        };
+
+        if host.is_empty()
+        {
+            return Err(format!("{address} has no host"));
+        }
+
+        //hostnames and ipv4 addresses, anything else cant be resolved anyway
+        if !host.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+        {
+            return Err(format!("{host} in {address} is not a valid host"));
+        }
+
+        (host.to_string(), address_port)
+    };
+
+    let port = match address_port
+    {
+        Some(address_port) => parse_port(address_port).map_err(|err| format!("{err} in {address}"))?.get(),
+        None => port
+    };
+
+    Ok(format!("{host}:{port}"))
+}
+
+//stops without a position get spread evenly between the ones around them, with
+//the ends at 0 and 1, none of them having a position keeps the plain even spacing
+fn fill_positions(positions: &[Option<f32>]) -> Result<Option<Vec<f32>>, String>
+{
+    if positions.iter().all(|position| position.is_none())
+    {
+        return Ok(None);
+    }
+
+    let last = positions.len() - 1;
+
+    let mut filled = Vec::with_capacity(positions.len());
+
+    let mut index = 0;
+    while index<positions.len()
+    {
+        if let Some(position) = positions[index]
+        {
+            if filled.last().is_some_and(|previous| *previous>position)
+            {
+                return Err(format!("color {} is at {position} which is before the previous color, positions have to go up", index + 1));
+            }
+
+            filled.push(position);
+            index += 1;
+            continue;
+        }
+
+        let start = filled.last().copied().unwrap_or(0.0);
+
+        let end_index = (index..=last).find(|index| positions[*index].is_some());
+        let end = end_index.and_then(|index| positions[index]).unwrap_or(1.0);
+
+        //the ends sit right on 0 and 1, gaps in the middle have known stops on both sides
+        let steps = end_index.unwrap_or(last + 1) - index;
+        let (offset, divisions) = match (index==0, end_index.is_none())
+        {
+            (true, true) => (0, steps - 1),
+            (true, false) => (0, steps),
+            (false, true) => (1, steps),
+            (false, false) => (1, steps + 1)
+        };
+
+        for step in 0..steps
+        {
+            let amount = if divisions==0
+            {
+                0.0
+            } else
+            {
+                (step + offset) as f32 / divisions as f32
+            };
+
+            filled.push(start + (end - start)*amount);
+        }
+
+        index += steps;
+    }
+
+    Ok(Some(filled))
+}
+
+#[derive(Deserialize)]
+struct Preset
+{
+    colors: Vec<String>,
+    interpolation: Option<String>
+}
+
+impl Preset
+{
+    pub fn load(path: &str, name: &str) -> Result<Self, String>
+    {
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("could not read {path}: {err}"))?;
+
+        let mut presets: HashMap<String, Preset> = toml::from_str(&text)
+            .map_err(|err| format!("could not parse {path}: {err}"))?;
+
+        presets.remove(name).ok_or_else(|| format!("no preset named {name} in {path}"))
+    }
+
+    pub fn colors(&self, clamp: bool, warnings: &mut Vec<String>) -> Result<Vec<(Color, Option<f32>)>, String>
+    {
+        ColorParser::new(self.colors.join(";"), clamp).parse(warnings)
+    }
+}
+
+struct PreviewConfig
+{
+    colors: Vec<Color>,
+    colorer: ColorerSettings,
+    tags: bool
+}
+
+enum Command
+{
+    Proxy(ProxyConfig),
+    Preview(PreviewConfig),
+    Version
+}
+
+struct Config
+{
+    command: Command,
+    log_level: LogLevel,
+    //printed once the log level is set so -q can hide them
+    warnings: Vec<String>
 }
 
-impl ColorParser
-{
-    pub fn new(colors: String) -> Self
-    {
-        let colors = colors.split([',', ';']).map(|s| s.to_string()).collect();
-        ColorParser{colors}
-    }
+impl Config
+{
+    //flags that dont mean anything outside of the proxy subcommand
+    const PROXY_FLAGS: &'static [&'static str] = &[
+        "-c", "--connect-address", "--server-port", "--socks5", "-l", "--listen-address",
+        "--allow-remote-clients", "-p", "--port", "--color-incoming", "--color-system",
+        "--color-combat-text", "--timeout", "--once", "--max-length", "--echo", "--dump",
+        "--max-connections", "--no-color", "--nodelay", "--banner", "--profile", "--game-version"
+        ];
+
+    pub fn parse(args: impl Iterator<Item=String>) -> Result<Self, String>
+    {
+        let mut args = args.skip(1).peekable();
+
+        //no subcommand means the proxy so older command lines keep working
+        let subcommand = args.next_if(|arg| !arg.starts_with('-'));
+        let mut preview = match subcommand.as_deref()
+        {
+            Some("preview") => true,
+            Some("proxy") | None => false,
+            Some(subcommand) => return Err(format!("unknown subcommand: {subcommand}"))
+        };
+
+        let mut options = Options::default();
+
+        let mut proxy_flag = None;
+
+        let mut config_file = None;
+        let mut unset = Vec::new();
+
+        let mut warnings = Vec::new();
+
+        let mut tags = false;
+        let mut stdin = false;
+        let mut preview_flag = false;
+
+        while let Some(arg) = args.next()
+        {
+            //one config file can be shared between the subcommands so only the command line is checked
+            if Self::PROXY_FLAGS.contains(&arg.as_str())
+            {
+                proxy_flag.get_or_insert(arg.clone());
+            }
+
+            let mut value = ||
+            {
+                args.next().ok_or(format!("{arg} has no argument"))
+            };
+
+            match arg.as_str()
+            {
+                "-c" | "--connect-address" =>
+                {
+                    let addresses = value()?;
+
+                    options.connect_address.get_or_insert_with(Vec::new).push(addresses);
+                },
+                "--server-port" =>
+                {
+                    options.server_port = Some(parse_port(&value()?)?);
+                },
+                "--allow-remote-clients" =>
+                {
+                    options.allow_remote_clients = Some(true);
+                },
+                "-l" | "--listen-address" =>
+                {
+                    let address = value()?;
+
+                    options.listen_address = Some(address.parse()
+                        .map_err(|err| format!("{address} is not a valid ip address: {err}"))?);
+                },
+                "-C" | "--colors" =>
+                {
+                    options.colors = Some(vec![value()?]);
+                },
+                "--clamp" =>
+                {
+                    options.clamp = Some(true);
+                },
+                "--colors-file" =>
+                {
+                    options.colors_file = Some(value()?);
+                },
+                "--gradient-image" =>
+                {
+                    options.gradient_image = Some(value()?);
+                },
+                "--solid" =>
+                {
+                    options.solid = Some(value()?);
+                },
+                "--preset" =>
+                {
+                    options.preset = Some(value()?);
+                },
+                "--preset-file" =>
+                {
+                    options.preset_file = Some(value()?);
+                },
+                "--rainbow" =>
+                {
+                    options.rainbow = Some(true);
+                },
+                "--stops" =>
+                {
+                    options.stops = Some(value()?
+                        .parse().map_err(|err| format!("{err} cannot be converted to stops amount"))?);
+                },
+                "-s" | "--shift" =>
+                {
+                    options.shift = Some(false);
+                },
+                "--shift-amount" =>
+                {
+                    options.shift_amount = Some(value()?
+                        .parse().map_err(|err| format!("{err} cannot be converted to shift amount"))?);
+                },
+                "--interpolation-steps" =>
+                {
+                    options.interpolation_steps = Some(value()?
+                        .parse().map_err(|err| format!("{err} cannot be converted to steps"))?);
+                },
+                "--strip-control" =>
+                {
+                    options.strip_control = Some(true);
+                },
+                "--newline-reset" =>
+                {
+                    let reset = value()?;
+
+                    options.newline_reset = match reset.to_lowercase().as_str()
+                    {
+                        "on" => Some(true),
+                        "off" => Some(false),
+                        _ => return Err(format!("{arg} must be on or off"))
+                    };
+                },
+                "--fade-to" =>
+                {
+                    options.fade_to = Some(value()?);
+                },
+                "--accent" =>
+                {
+                    options.accent = Some(value()?
+                        .parse().map_err(|err| format!("{err} cannot be converted to accent length"))?);
+                },
+                "--tag-format" =>
+                {
+                    options.tag_format = Some(value()?);
+                },
+                "--palette-quantize" =>
+                {
+                    options.palette_quantize = Some(value()?);
+                },
+                "--hue-shift" =>
+                {
+                    options.hue_shift = Some(value()?
+                        .parse().map_err(|err| format!("{err} cannot be converted to degrees"))?);
+                },
+                "--jitter" =>
+                {
+                    options.jitter = Some(value()?
+                        .parse().map_err(|err| format!("{err} cannot be converted to jitter amount"))?);
+                },
+                "--animate" =>
+                {
+                    options.animate = Some(value()?
+                        .parse().map_err(|err| format!("{err} cannot be converted to animation speed"))?);
+                },
+                "-i" | "--interpolation" =>
+                {
+                    options.interpolation = Some(value()?);
+                },
+                "--gamma" =>
+                {
+                    options.gamma = Some(value()?
+                        .parse().map_err(|err| format!("{err} cannot be converted to gamma"))?);
+                },
+                "-e" | "--easing" =>
+                {
+                    options.easing = Some(value()?);
+                },
+                "--reverse" =>
+                {
+                    options.reverse = Some(true);
+                },
+                "-p" | "--port" =>
+                {
+                    options.port = Some(parse_port(&value()?)?);
+                },
+                "-r" | "--repeat" =>
+                {
+                    options.repeat = Some(value()?
+                        .parse().map_err(|err| format!("{err} cannot be converted to repeat amount"))?);
+                },
+                "--repeat-mode" =>
+                {
+                    options.repeat_mode = Some(value()?);
+                },
+                "--color-commands" =>
+                {
+                    options.color_commands = Some(true);
+                },
+                "--per-word" =>
+                {
+                    options.per_word = Some(true);
+                },
+                "--color-spaces" =>
+                {
+                    options.color_spaces = Some(true);
+                },
+                "--seed" =>
+                {
+                    options.seed = Some(value()?
+                        .parse().map_err(|err| format!("{err} cannot be converted to seed"))?);
+                },
+                "--color-incoming" =>
+                {
+                    options.color_incoming = Some(true);
+                },
+                "--color-system" =>
+                {
+                    options.color_system = Some(true);
+                },
+                "--color-combat-text" =>
+                {
+                    options.color_combat_text = Some(true);
+                },
+                "--timeout" =>
+                {
+                    options.timeout = Some(value()?
+                        .parse().map_err(|err| format!("{err} cannot be converted to timeout"))?);
+                },
+                "--max-length" =>
+                {
+                    options.max_length = Some(value()?
+                        .parse().map_err(|err| format!("{err} cannot be converted to max length"))?);
+                },
+                "--socks5" =>
+                {
+                    options.socks5 = Some(value()?);
+                },
+                "--dump" =>
+                {
+                    options.dump = Some(value()?);
+                },
+                "--echo" =>
+                {
+                    options.echo = Some(true);
+                },
+                "--max-connections" =>
+                {
+                    options.max_connections = Some(value()?
+                        .parse().map_err(|err| format!("{err} cannot be converted to max connections"))?);
+                },
+                "--no-color" =>
+                {
+                    options.color = Some(false);
+                },
+                "--game-version" =>
+                {
+                    options.game_version = Some(value()?);
+                },
+                "--nodelay" =>
+                {
+                    options.nodelay = Some(true);
+                },
+                "--banner" =>
+                {
+                    options.banner = Some(value()?);
+                },
+                "--profile" =>
+                {
+                    options.profile = Some(true);
+                },
+                "--once" =>
+                {
+                    options.once = Some(true);
+                },
+                //the last one of these wins, even over the file
+                "-v" | "--verbose" =>
+                {
+                    options.verbose = Some(true);
+                    options.quiet = Some(false);
+                },
+                "-q" | "--quiet" =>
+                {
+                    options.quiet = Some(true);
+                    options.verbose = Some(false);
+                },
+                "--config" =>
+                {
+                    config_file = Some(value()?);
+                },
+                "--unset" =>
+                {
+                    unset.push(value()?);
+                },
+                "--tags" =>
+                {
+                    tags = true;
+                },
+                "--stdin" =>
+                {
+                    stdin = true;
+                },
+                "--preview" =>
+                {
+                    preview_flag = true;
+                },
+                "-V" | "--version" =>
+                {
+                    return Ok(Config{command: Command::Version, log_level: LogLevel::Info, warnings});
+                },
+                opt =>
+                {
+                    return Err(format!("unknown option: {opt}"));
+                }
+            }
+        }
+
+        if let Some(path) = config_file
+        {
+            options = options.or(Options::load(&path, &unset, &mut warnings)?);
+        } else if !unset.is_empty()
+        {
+            warnings.push("--unset does nothing without --config".to_string());
+        }
+
+        let log_level = if options.verbose==Some(true)
+        {
+            LogLevel::Debug
+        } else if options.quiet==Some(true)
+        {
+            LogLevel::Error
+        } else
+        {
+            LogLevel::Info
+        };
+
+        //the old flags for the preview subcommand, --stdin printing tags and --preview colors
+        if stdin || preview_flag
+        {
+            if subcommand.as_deref()==Some("proxy")
+            {
+                let flag = if stdin
+                {
+                    "--stdin"
+                } else
+                {
+                    "--preview"
+                };
+
+                return Err(format!("{flag} doesnt work with the proxy subcommand"));
+            }
+
+            preview = true;
+            tags |= !preview_flag;
+        }
+
+        let server_port = options.server_port.map_or(7777, NonZeroU16::get);
+
+        let mut connect_addresses: Vec<String> = options.connect_address.unwrap_or_default().iter()
+            .flat_map(|addresses| addresses.split(','))
+            .map(|address| address.trim())
+            .filter(|address| !address.is_empty())
+            .map(|address| address.to_string())
+            .collect();
+
+        if preview
+        {
+            if let Some(flag) = proxy_flag
+            {
+                return Err(format!("{flag} only works with the proxy subcommand"));
+            }
+        } else
+        {
+            if tags
+            {
+                return Err("--tags only works with the preview subcommand".to_string());
+            }
+
+            if connect_addresses.is_empty()
+            {
+                return Err("must have -c or --connect-address option specified".to_string());
+            }
+
+            connect_addresses = connect_addresses.iter()
+                .map(|address| with_server_port(address, server_port))
+                .collect::<Result<_, _>>()?;
+        }
+
+        let stops = options.stops.unwrap_or(6);
+        if stops<2
+        {
+            return Err("--stops must be at least 2".to_string());
+        }
+
+        let shift_amount = options.shift_amount.unwrap_or(1.0);
+        if !(0.0..=1.0).contains(&shift_amount)
+        {
+            return Err("--shift-amount must be between 0 and 1".to_string());
+        }
+
+        if options.interpolation_steps.is_some_and(|steps| steps<2)
+        {
+            return Err("--interpolation-steps must be at least 2".to_string());
+        }
+
+        if options.accent==Some(0)
+        {
+            return Err("--accent must be at least 1".to_string());
+        }
+
+        let hue_shift = options.hue_shift.unwrap_or(0.0);
+        if !hue_shift.is_finite()
+        {
+            return Err("--hue-shift must be a number".to_string());
+        }
+
+        let positive = |name: &str, value: Option<f32>| -> Result<(), String>
+        {
+            match value
+            {
+                Some(value) if !(value>0.0 && value.is_finite()) =>
+                {
+                    Err(format!("{name} must be a positive number"))
+                },
+                _ => Ok(())
+            }
+        };
+
+        positive("--animate", options.animate)?;
+        positive("--gamma", options.gamma)?;
+        positive("--repeat", options.repeat)?;
+        positive("--timeout", options.timeout)?;
+
+        let max_length = options.max_length.unwrap_or(MAX_MESSAGE_LENGTH);
+        if max_length==0 || max_length>MAX_MESSAGE_LENGTH
+        {
+            return Err(format!("--max-length must be between 1 and {MAX_MESSAGE_LENGTH}"));
+        }
+
+        if options.max_connections==Some(0)
+        {
+            return Err("--max-connections must be at least 1".to_string());
+        }
+
+        let clamp = options.clamp.unwrap_or(false);
+        let shift = options.shift.unwrap_or(true);
+        let newline_reset = options.newline_reset.unwrap_or(true);
+        let repeat = options.repeat.unwrap_or(1.0);
+
+        let color_incoming = options.color_incoming.unwrap_or(false);
+        let color_system = options.color_system.unwrap_or(false);
+
+        let mut no_color = !options.color.unwrap_or(true);
+        if let Some(version) = options.game_version
+        {
+            match supports_color_tags(&version)
+            {
+                Ok(true) => (),
+                Ok(false) =>
+                {
+                    warnings.push(format!("terraria {version} has no color tags, chat is forwarded uncolored"));
+
+                    no_color = true;
+                },
+                //mods and betas name their versions however they like, those are most likely new enough
+                Err(err) => warnings.push(format!("{err}, coloring chat anyway"))
+            }
+        }
+
+        let mut colors = match options.colors
+        {
+            Some(colors_list) =>
+            {
+                let parsed = ColorParser::new(colors_list.join(";"), clamp).parse(&mut warnings)?;
+                if parsed.is_empty()
+                {
+                    return Err("-C or --colors has no colors".to_string());
+                }
 
-    pub fn parse(&self) -> Result<Vec<Color>, String>
-    {
-        let mut colors = self.colors.iter();
+                Some(parsed)
+            },
+            None => None
+        };
 
-        let mut parsed = Vec::new();
-        while let Some(r) = colors.next()
+        if options.animate.is_some() && !shift
         {
-            let r = r.as_str();
-            let g = colors.next().ok_or_else(|| "no green value".to_string())?;
-            let b = colors.next().ok_or_else(|| "no blue value".to_string())?;
+            return Err("cant use --animate with -s or --shift, animating moves the shift".to_string());
+        }
 
-            parsed.push(Color::try_from([r, g, b])?);
+        if color_system && !color_incoming
+        {
+            warnings.push("--color-system does nothing without --color-incoming".to_string());
         }
 
-        Ok(parsed)
-    }
-}
+        if shift_amount!=1.0 && (!shift || options.animate.is_some())
+        {
+            warnings.push("--shift-amount does nothing with -s, --shift or --animate".to_string());
+        }
 
-struct Config
-{
-    connect_address: String,
-    colors: Vec<Color>,
-    shift: bool,
-    interpolation: Interpolation,
-    port: u32,
-    repeat: f32
-}
+        if !newline_reset && (!shift || options.animate.is_some())
+        {
+            warnings.push("--newline-reset does nothing with -s, --shift or --animate".to_string());
+        }
 
-impl Config
-{
-    pub fn parse(args: impl Iterator<Item=String>) -> Result<Self, String>
-    {
-        let mut connect_address = String::new();
+        if let Some(path) = options.colors_file
+        {
+            if colors.is_some()
+            {
+                return Err("cant use both --colors-file and -C or --colors".to_string());
+            }
 
-        let mut colors = vec![
-            Color::new(255, 0, 0),
-            Color::new(0, 255, 0),
-            Color::new(0, 0, 255)
-            ];
+            colors = Some(load_colors_file(&path, clamp, &mut warnings)?);
+        }
 
-        let mut shift = true;
-        let mut interpolation = Interpolation::Linear;
-        let mut port = 8888;
-        let mut repeat = 1.0;
+        if let Some(path) = options.gradient_image
+        {
+            if colors.is_some()
+            {
+                return Err("cant use --gradient-image with -C, --colors or --colors-file".to_string());
+            }
 
-        let mut args = args.skip(1);
-        while let Some(arg) = args.next()
+            colors = Some(load_gradient_image(&path, stops)?);
+        }
+
+        if options.rainbow==Some(true)
         {
-            match arg.as_str()
+            if colors.is_some() || options.preset.is_some()
             {
-                "-c" | "--connect-address" =>
-                {
-                    connect_address = args.next().ok_or(format!("{arg} has no argument"))?;
-                },
-                "-C" | "--colors" =>
-                {
-                    let colors_list = args.next().ok_or(format!("{arg} has no argument"))?;
+                return Err("cant use --rainbow with -C, --colors, --colors-file, --gradient-image or a preset".to_string());
+            }
 
-                    colors = ColorParser::new(colors_list).parse()?;
-                    if colors.is_empty()
-                    {
-                        return Err(format!("{arg} has no colors"));
-                    }
-                },
-                "-s" | "--shift" =>
-                {
-                    shift = false;
-                },
-                "-i" | "--interpolation" =>
-                {
-                    let interp_type = args.next().ok_or(format!("{arg} has no argument"))?;
-                    interpolation = match interp_type.to_lowercase().as_str()
-                    {
-                        "random" => Ok(Interpolation::Random),
-                        "nearest" => Ok(Interpolation::Nearest),
-                        "linear" => Ok(Interpolation::Linear),
-                        "cubic" => Ok(Interpolation::Cubic),
-                        _ => Err(format!("{arg} is not a valid interpolation"))
-                    }?;
-                },
-                "-p" | "--port" =>
-                {
-                    port = args.next().ok_or(format!("{arg} has no argument"))?
-                        .parse().map_err(|err| format!("{err} cannot be converted to port"))?;
-                },
-                "-r" | "--repeat" =>
-                {
-                    repeat = args.next().ok_or(format!("{arg} has no argument"))?
-                        .parse().map_err(|err| format!("{err} cannot be converted to repeat amount"))?;
-                },
-                opt =>
-                {
-                    return Err(format!("unknown option: {opt}"));
-                }
+            colors = Some(Color::rainbow(stops).into_iter().map(|color| (color, None)).collect());
+        }
+
+        let mut interpolation = options.interpolation.as_deref()
+            .map(Interpolation::try_from)
+            .transpose()?;
+
+        if let Some(name) = options.preset
+        {
+            if colors.is_some()
+            {
+                return Err("cant use both a preset and -C, --colors, --colors-file or --gradient-image".to_string());
+            }
+
+            let preset_file = options.preset_file.as_deref().unwrap_or("presets.toml");
+            let preset = Preset::load(preset_file, &name)?;
+
+            let parsed = preset.colors(clamp, &mut warnings)?;
+            if parsed.is_empty()
+            {
+                return Err(format!("preset {name} has no colors"));
+            }
+
+            colors = Some(parsed);
+
+            //an explicit -i still wins over the presets interpolation
+            if let (None, Some(preset_interpolation)) = (&interpolation, preset.interpolation)
+            {
+                interpolation = Some(Interpolation::try_from(preset_interpolation.as_str())?);
+            }
+        }
+
+        if let Some(color) = options.solid
+        {
+            let color = ColorParser::new(color, clamp).parse_single(&mut warnings)
+                .map_err(|err| format!("--solid: {err}"))?;
+
+            if colors.is_some()
+            {
+                return Err("cant use --solid with any other way of picking colors".to_string());
+            }
+
+            //a single color has nothing to interpolate or repeat
+            if interpolation.is_some() || repeat!=1.0
+            {
+                warnings.push("--solid ignores -i, --interpolation, -r and --repeat".to_string());
+            }
+
+            colors = Some(vec![(color, None)]);
+        }
+
+        let (colors, positions): (Vec<_>, Vec<_>) = colors.unwrap_or_else(||
+        {
+            vec![
+                (Color::new(255, 0, 0), None),
+                (Color::new(0, 255, 0), None),
+                (Color::new(0, 0, 255), None)
+                ]
+        }).into_iter().unzip();
+
+        let positions = fill_positions(&positions)?;
+
+        let mut interpolation = interpolation.unwrap_or(Interpolation::Linear);
+
+        if let Some(gamma) = options.gamma
+        {
+            if let Interpolation::LinearGamma(current) = &mut interpolation
+            {
+                *current = gamma;
+            } else
+            {
+                warnings.push("--gamma only does something with the linear-gamma interpolation".to_string());
+            }
+        }
+
+        let interpolation = Arc::new(interpolation);
+
+        //the game only has the one color tag, older versions and mods are more
+        //likely to handle the colors the game uses itself
+        let mut palette = match options.tag_format.as_deref().map(str::to_lowercase).as_deref()
+        {
+            None | Some("hex") => None,
+            Some("palette") => Some(Color::game_palette()),
+            Some(format) => return Err(format!("{format} is not a valid tag format"))
+        };
+
+        if let Some(colors) = options.palette_quantize
+        {
+            if palette.is_some()
+            {
+                return Err("cant use --palette-quantize with --tag-format palette".to_string());
+            }
+
+            let colors = ColorParser::new(colors, clamp).parse(&mut warnings)
+                .map_err(|err| format!("--palette-quantize: {err}"))?;
+
+            if colors.iter().any(|(_, position)| position.is_some())
+            {
+                return Err("--palette-quantize colors cant have a position".to_string());
             }
+
+            palette = Some(colors.into_iter().map(|(color, _)| color).collect());
         }
 
-        if connect_address.is_empty()
+        let fade_to = options.fade_to.map(|color| ColorParser::new(color, clamp).parse_single(&mut warnings)
+            .map_err(|err| format!("--fade-to: {err}"))).transpose()?;
+
+        let colorer = ColorerSettings{
+            shift,
+            shift_amount,
+            interpolation,
+            steps: options.interpolation_steps,
+            repeat,
+            repeat_mode: options.repeat_mode.as_deref().map(RepeatMode::try_from).transpose()?.unwrap_or(RepeatMode::Wrap),
+            color_commands: options.color_commands.unwrap_or(false),
+            per_word: options.per_word.unwrap_or(false),
+            color_spaces: options.color_spaces.unwrap_or(false),
+            newline_reset,
+            strip_control: options.strip_control.unwrap_or(false),
+            seed: options.seed,
+            easing: options.easing.as_deref().map(Easing::try_from).transpose()?.unwrap_or(Easing::Linear),
+            reverse: options.reverse.unwrap_or(false),
+            animate: options.animate,
+            fade_to,
+            jitter: options.jitter.unwrap_or(0),
+            hue_shift,
+            palette,
+            positions,
+            accent: options.accent
+            };
+
+        if preview
         {
-            return Err("must have -c or --connect-address option specified".to_string());
+            let preview = PreviewConfig{colors, colorer, tags};
+
+            return Ok(Config{command: Command::Preview(preview), log_level, warnings});
         }
 
-        Ok(Config{connect_address, colors, shift, interpolation, port, repeat})
+        let proxy = ProxyConfig{
+            connect_addresses,
+            listen_address: options.listen_address.unwrap_or(Ipv4Addr::LOCALHOST.into()),
+            allow_remote_clients: options.allow_remote_clients.unwrap_or(false),
+            port: options.port.map_or(8888, NonZeroU16::get),
+            once: options.once.unwrap_or(false),
+            timeout: options.timeout.map(Duration::from_secs_f32),
+            color_incoming,
+            color_system,
+            color_combat_text: options.color_combat_text.unwrap_or(false),
+            echo: options.echo.unwrap_or(false),
+            max_length,
+            dump: options.dump,
+            socks5: options.socks5,
+            max_connections: options.max_connections,
+            no_color,
+            nodelay: options.nodelay.unwrap_or(false),
+            profile: options.profile.unwrap_or(false),
+            banner: options.banner,
+            colors,
+            colorer
+            };
+
+        Ok(Config{command: Command::Proxy(proxy), log_level, warnings})
     }
 }
 
-fn help_message() -> !
+fn help_message()
 {
     let executable = env::args().next().unwrap();
-    eprintln!("usage: {executable} [args]");
+    eprintln!("usage: {executable} [proxy|preview] [args]");
+    eprintln!(" subcommands:");
+    eprintln!("    proxy                    color chat going through to a server (default)");
+    eprintln!("    preview                  color lines from stdin and show them in the terminal");
     eprintln!(" args:");
-    eprintln!("    -c, --connect-address    address to connect to");
+    eprintln!("    -c, --connect-address    address to connect to, ipv6 addresses go in brackets like [::1]:7777");
+    eprintln!("                             can be repeated or comma separated, later ones are backups");
+    eprintln!("    --server-port            port used for connect addresses that dont have one (default 7777)");
+    eprintln!("    --socks5                 connect to the server through a socks5 proxy at this address (no authentication)");
+    eprintln!("    -l, --listen-address     address to listen for the game on, :: listens on ipv4 and ipv6 (default 127.0.0.1)");
+    eprintln!("    --allow-remote-clients   needed to listen on anything other than loopback, other devices can send chat as u then");
     eprintln!("    -C, --colors             gradient to use (default 255, 0, 0; 0, 255, 0; 0, 0, 255)");
+    eprintln!("                             a color can be placed at a position from 0 to 1 like 255, 0, 0@0.2 or red@0.2");
+    eprintln!("    --clamp                  clamp color values outside of 0 to 255 instead of failing");
+    eprintln!("    --colors-file            read the gradient from a file, one color per line, # starts a comment");
+    eprintln!("    --gradient-image         sample the gradient from the top row of a png, --stops picks how many colors");
+    eprintln!("    --solid                  color the whole message with a single color");
+    eprintln!("    --preset                 use a named gradient from the presets file");
+    eprintln!("    --preset-file            file to load presets from (default presets.toml)");
+    eprintln!("    --rainbow                use a rainbow gradient going through every hue");
+    eprintln!("    --stops                  amount of colors in the rainbow or taken from --gradient-image (default 6)");
     eprintln!("    -s, --shift              dont shift the colors randomly");
+    eprintln!("    --shift-amount           how far along the gradient the random shift can go from 0 to 1, 0 is the same as -s (default 1)");
+    eprintln!("    --newline-reset          off keeps the same random shift for every message instead of rolling a new one (default on)");
+    eprintln!("    --fade-to                blend transparent colors like #ff000080 toward this background color");
+    eprintln!("    --accent                 only color this many letters and the rest of the word theyre in, 1 colors the first word");
+    eprintln!("    --tag-format             hex uses any color, palette snaps every color to the closest one the game uses itself (default hex)");
+    eprintln!("    --palette-quantize       snap every color to the closest one in this list, given like a gradient");
+    eprintln!("    --hue-shift              turn the hue of every color by this many degrees, for reusing a gradient in other colors");
+    eprintln!("    --jitter                 randomly move every letters color by up to this much (0 to 255) for a sparkly look");
+    eprintln!("    --animate                move the shift over time instead, going through the gradient this many times a second");
     eprintln!("    -i, --interpolation      interpolation type (see below, default linear)");
+    eprintln!("    --gamma                  gamma used by the linear-gamma interpolation (default 2.2)");
+    eprintln!("    --interpolation-steps    split the gradient into this many flat bands instead of a smooth blend");
+    eprintln!("    -e, --easing             how the gradient progresses along the message (see below, default linear)");
+    eprintln!("    --reverse                run the gradient from the last color to the first");
     eprintln!("    -p, --port               proxy port (default 8888)");
-    eprintln!("    -r, --repeat             amount of times to repeat the gradient, can be fractional (default 1)");
+    eprintln!("    -r, --repeat             amount of times to repeat the gradient, must be positive, can be fractional (default 1)");
+    eprintln!("    --repeat-mode            wrap jumps back to the first color on every repeat, mirror goes back and forth (default wrap)");
+    eprintln!("    --color-commands         color messages starting with / too");
+    eprintln!("    --per-word               give every word its own gradient");
+    eprintln!("    --color-spaces           put spaces in tags too, they can look like gaps on some backgrounds otherwise");
+    eprintln!("    --strip-control          remove control characters from messages and turn line breaks into spaces, otherwise theyre left uncolored");
+    eprintln!("    --color-incoming         also color chat from other players (only on ur screen)");
+    eprintln!("    --color-system           with --color-incoming also color messages from the server itself like join notices");
+    eprintln!("    --color-combat-text      color the floating text over players and enemies like damage numbers (only on ur screen)");
+    eprintln!("    --seed                   seed for the random shift, random interpolation and jitter");
+    eprintln!("    --timeout                seconds without any data before a connection is dropped (default none)");
+    eprintln!("    --max-connections        reject new connections while this many are open (default unlimited)");
+    eprintln!("    --no-color               forward chat without coloring it, for checking if the proxy causes a problem");
+    eprintln!("    --game-version           version of the game like 1.4.4, before 1.3 theres no color tags so chat is left alone");
+    eprintln!("                             every newer version gets the same tags with neighbouring letters of the same color sharing one");
+    eprintln!("    --nodelay                send packets right away instead of letting the os batch them, can lower chat lag");
+    eprintln!("    --banner                 message shown (colored) to whoever connects once theyre in the world");
+    eprintln!("    --profile                time how long coloring takes, printed for every message with -v and on average at exit");
+    eprintln!("    --once                   exit after the first session ends");
+    eprintln!("    --max-length             biggest colored message in bytes, longer ones are sent uncolored (default {MAX_MESSAGE_LENGTH})");
+    eprintln!("    --dump                   write every raw packet going through to a file, see the readme for the format");
+    eprintln!("    --echo                   print every chat message sent before its colored");
+    eprintln!("    -v, --verbose            also print details about every chat message");
+    eprintln!("    -q, --quiet              only print errors");
+    eprintln!("    --tags                   make preview print the colored tags instead of showing the colors");
+    eprintln!("    --stdin                  same as preview --tags");
+    eprintln!("    --preview                same as preview");
+    eprintln!("    --config                 load options from a toml file, see the readme for the format");
+    eprintln!("    --unset                  ignore a key from the config file, like --unset clamp, can be repeated");
+    eprintln!("    -V, --version            print the version and exit");
     eprintln!(" gradients:");
     eprintln!("    gradients are lists of 3 values (rgb) separated by , or ;");
+    eprintln!("    colors can also be written in hex like #ff8800 or #f80");
+    eprintln!("    or as hsv(hue, saturation, value) with the hue from 0 to 360 and the rest from 0 to 100");
+    eprintln!("    or by css name (red, green, blue, cyan, magenta, yellow, white, black, gray,");
+    eprintln!("    orange, purple, pink, brown, gold, lime, navy, teal, violet)");
     eprintln!("    example:");
     eprintln!("     shifts from red (255,0,0) to blue (0,0,255)");
     eprintln!("     255, 0, 0; 0, 0, 255");
+    eprintln!("     255, 0, 0; #0000ff");
+    eprintln!("     red; blue");
+    eprintln!(" presets:");
+    eprintln!("    the presets file maps names to a list of colors and an optional interpolation");
+    eprintln!("    example:");
+    eprintln!("     [fire]");
+    eprintln!("     colors = [\"red\", \"orange\", \"#ffff00\"]");
+    eprintln!("     interpolation = \"cubic\"");
     eprintln!(" interpolations:");
-    eprintln!("    available interpolation types are: random, nearest, linear, cubic");
-    process::exit(1);
+    eprintln!("    available interpolation types are: random, nearest, linear, smoothstep, bias, linear-gamma, cubic, hsv, oklch");
+    eprintln!("    bias takes an amount between 0 and 1 like bias:0.3, lower stays on each color longer (default 0.5)");
+    eprintln!(" easings:");
+    eprintln!("    available easing types are: linear, ease-in, ease-out, ease-in-out, sine");
 }
 
-fn main()
+//errors come back up to here so the exit code is decided in one place
+fn main() -> ExitCode
 {
-    let config = Config::parse(env::args()).unwrap_or_else(|err|
+    let config = match Config::parse(env::args())
+    {
+        Ok(config) => config,
+        Err(err) =>
         {
             eprintln!("error: {err}\n");
             help_message();
-        });
 
-    start_listening(&config).unwrap_or_else(|err|
+            return ExitCode::FAILURE;
+        }
+    };
+
+    terraria_cool_colorer::set_log_level(config.log_level);
+
+    if config.log_level>=LogLevel::Info
     {
-        eprintln!("error: {err}");
-    });
-}
+        for warning in &config.warnings
+        {
+            eprintln!("warning: {warning}");
+        }
+    }
 
-fn start_listening(config: &Config) -> Result<(), String>
-{
-    let listen_address = format!("127.0.0.1:{}", config.port);
+    let proxy = match config.command
+    {
+        Command::Proxy(proxy) => proxy,
+        Command::Preview(preview) =>
+        {
+            if let Err(err) = color_stdin(preview)
+            {
+                eprintln!("{err}");
+
+                return ExitCode::FAILURE;
+            }
+
+            return ExitCode::SUCCESS;
+        },
+        Command::Version =>
+        {
+            println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
 
-    let listener = TcpListener::bind(&listen_address)
-        .map_err(|err| format!("could not start a local server on {listen_address}: {err}"))?;
+            return ExitCode::SUCCESS;
+        }
+    };
 
-    println!("listening for incoming connections: {listen_address}");
+    //finishes the current packets instead of dying halfway through one
+    if let Err(err) = ctrlc::set_handler(terraria_cool_colorer::shutdown)
+    {
+        eprintln!("error setting the ctrl-c handler: {err}");
+    }
 
-    for stream in listener.incoming()
+    if let Err(err) = terraria_cool_colorer::run_proxy(&proxy)
     {
-        let mut write_stream = stream.map_err(|err| format!("could not establish connection: {err}"))?;
+        eprintln!("error: {err}");
 
-        let mut write_connector = TcpStream::connect(&config.connect_address)
-            .map_err(|err| format!("could not connect to {}: {err}", &config.connect_address))?;
+        return ExitCode::FAILURE;
+    }
 
-        let mut read_stream = write_stream.try_clone()
-            .map_err(|err| format!("error cloning client stream: {err}"))?;
-        let mut read_connector = write_connector.try_clone()
-            .map_err(|err| format!("error cloning server stream: {err}"))?;
+    ExitCode::SUCCESS
+}
 
-        let colorer =
-            Colorer::new(
-                config.colors.clone(),
-                config.shift,
-                config.interpolation.clone(),
-                config.repeat
-                );
+fn color_stdin(config: PreviewConfig) -> Result<(), String>
+{
+    let mut colorer = Colorer::new(config.colors, config.colorer);
 
-        thread::spawn(move ||
-        {
-            ClientReader::spawn(&mut read_stream, &mut write_connector, colorer)
-                .listen_connection();
-        });
+    for line in io::stdin().lines()
+    {
+        let line = line.map_err(|err| format!("error reading stdin: {err}"))?;
+
+        let colored = colorer.color_text(&line);
 
-        thread::spawn(move ||
+        if config.tags
+        {
+            println!("{colored}");
+        } else
         {
-            ServerReader::spawn(&mut read_connector, &mut write_stream)
-                .listen_connection();
-        });
+            println!("{}", ansi_preview(&parse_colored(&colored)));
+        }
     }
 
     Ok(())
 }
 
-
-trait StreamReader
+//reads back the color tags, characters outside of them dont have a color
+fn parse_colored(text: &str) -> Vec<(char, Option<Color>)>
 {
-    fn read_stream(&mut self) -> &mut TcpStream;
+    let mut parsed = Vec::new();
 
-    fn handle_stream(
-        &mut self,
-        ) -> Result<Vec<u8>, String>
+    let mut rest = text;
+    while let Some(c) = rest.chars().next()
     {
-        let mut reader = BufReader::new(self.read_stream());
+        if let Some((color, text, after)) = parse_color_tag(rest)
+        {
+            parsed.extend(text.into_iter().map(|c| (c, Some(color))));
+
+            rest = after;
+            continue;
+        }
 
-        let buffer: Vec<u8> = reader.fill_buf()
-            .map_err(|err| format!("error reading stream: {err}"))?.to_vec();
-        Ok(self.handle_buffer(&buffer))
+        parsed.push((c, None));
+        rest = &rest[c.len_utf8()..];
     }
 
-    fn handle_buffer(&mut self, buffer: &[u8]) -> Vec<u8>;
+    parsed
 }
 
-trait ProxyPart<'a>: StreamReader
+fn parse_color_tag(text: &str) -> Option<(Color, Vec<char>, &str)>
 {
-    fn write_stream(&mut self) -> &mut TcpStream;
+    let (hex, tag_text) = text.strip_prefix("[c/")?.split_once(':')?;
+    let color = Color::from_hex(&format!("#{hex}")).ok()?;
+
+    let mut chars = Vec::new();
 
-    fn listen_connection(&mut self)
+    let mut iter = tag_text.char_indices().peekable();
+    while let Some((index, c)) = iter.next()
     {
-        loop
+        match c
         {
-            match self.handle_stream()
+            ']' => return Some((color, chars, &tag_text[index+1..])),
+            '\\' if iter.peek().is_some_and(|(_, next)| *next=='[' || *next==']') =>
             {
-                Ok(data) =>
-                {
-                    if let Err(err) = self.write_stream().write(&data)
-                    {
-                        if err.kind()==ErrorKind::BrokenPipe
-                        {
-                            println!("connection closed");
-                            process::exit(0);
-                        }
-                        println!("error writing to out: {err}");
-                        process::exit(1);
-                    };
+                //escaped bracket, only the bracket itself is shown
+                chars.push(iter.next().unwrap().1);
+            },
+            c => chars.push(c)
+        }
+    }
 
-                    self.write_stream().flush().unwrap();
-                },
-                Err(err) => println!("error reading in data: {err}")
+    None
+}
+
+fn ansi_preview(chars: &[(char, Option<Color>)]) -> String
+{
+    let mut preview = String::new();
+
+    for (c, color) in chars
+    {
+        match color
+        {
+            Some(color) =>
+            {
+                let _ = write!(preview, "\x1b[38;2;{};{};{}m{c}", color.r(), color.g(), color.b());
+            },
+            None =>
+            {
+                let _ = write!(preview, "\x1b[39m{c}");
             }
         }
     }
-}
 
+    preview.push_str("\x1b[0m");
 
-struct ClientReader<'a>
-{
-    read_stream: &'a mut TcpStream,
-    write_stream: &'a mut TcpStream,
-    colorer: Colorer
+    preview
 }
 
-impl<'a> ClientReader<'a>
+#[cfg(test)]
+mod tests
 {
-    pub fn spawn(
-        read_stream: &'a mut TcpStream,
-        write_stream: &'a mut TcpStream,
-        colorer: Colorer
-        ) -> Self
-    {
-        ClientReader{
-            read_stream,
-            write_stream,
-            colorer
-            }
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Config, String>
+    {
+        Config::parse(["terraria_cool_colorer"].iter().chain(args).map(|arg| arg.to_string()))
     }
 
-    const MINIMUM_SIZE: usize = 10;
+    #[test]
+    fn repeat_must_be_positive()
+    {
+        assert!(parse(&["preview", "-r", "2.5"]).is_ok());
 
-    const CHAT_MESSAGE_HEADER: [u8; 7] =
-        [0x52, 0x01, 0x00, 0x03, 0x53, 0x61, 0x79];
+        for repeat in ["0", "-1", "inf"]
+        {
+            assert!(parse(&["preview", "-r", repeat]).is_err());
+        }
+    }
 
-    const MESSAGE_POS: usize = 9;
+    fn parse_colors(colors: &str) -> Result<Vec<(Color, Option<f32>)>, String>
+    {
+        ColorParser::new(colors.to_string(), false).parse(&mut Vec::new())
+    }
 
-    fn change_chat(&mut self, buffer: &[u8]) -> Vec<u8>
+    #[test]
+    fn incomplete_colors()
     {
-        let full_length = buffer.len()-Self::MESSAGE_POS;
-        let length_length = if full_length>128
-        {
-            2
-        } else
-        {
-            1
-        };
+        let err = parse_colors("255,0").unwrap_err();
+        assert!(err.contains("color 1 (255, 0)") && err.contains("blue"), "{err}");
 
-        let real_msg_pos = Self::MESSAGE_POS+length_length;
+        let err = parse_colors("255,0,0,255").unwrap_err();
+        assert!(err.contains("color 2 (255)") && err.contains("green"), "{err}");
 
-        let message = String::from_utf8_lossy(&buffer[real_msg_pos..]);
-        println!("client sent: {}", message);
+        let err = parse_colors("255,,0,0").unwrap_err();
+        assert!(err.contains("color 1") && err.contains("empty"), "{err}");
 
-        let new_message = self.colorer.color_text(&message);
+        assert_eq!(parse_colors("255,0,0").unwrap(), vec![(Color::new(255, 0, 0), None)]);
+    }
+
+    #[test]
+    fn out_of_range_values()
+    {
+        let clamped = |colors: &str| ColorParser::new(colors.to_string(), true).parse(&mut Vec::new()).map(|parsed| parsed[0].0);
 
-        let new_length = new_message.bytes().len();
-        let mut encoded_length = Self::terraria_type(new_length as u32);
+        for (value, expected) in [("300", 255), ("-5", 0)]
+        {
+            let colors = format!("{value},0,0");
 
-        let mut out_vec = Vec::new();
+            let err = parse_colors(&colors).unwrap_err();
+            assert!(err.contains(&format!("{value} is outside of the 0 to 255 range")), "{err}");
 
-        //length of the payload
-        let payload_length = (Self::MESSAGE_POS+encoded_length.len()+new_length) as u16;
-        out_vec.extend(payload_length.to_le_bytes().into_iter());
+            assert_eq!(clamped(&colors), Ok(Color::new(expected, 0, 0)));
+        }
 
-        //the header
-        out_vec.extend(&Self::CHAT_MESSAGE_HEADER);
+        assert_eq!(parse_colors("255,0,0").map(|parsed| parsed[0].0), Ok(Color::new(255, 0, 0)));
+        assert_eq!(clamped("255,0,0"), Ok(Color::new(255, 0, 0)));
+    }
 
-        //length ("""encoded""" in the dumbest way, why????)
-        out_vec.append(&mut encoded_length);
+    #[test]
+    fn interlaced_gradient_image()
+    {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/interlaced.png");
 
-        //message
-        out_vec.extend(new_message.bytes());
+        //the top row goes from blue to red in steps of 36
+        let colors = load_gradient_image(path, 8).unwrap();
 
-        out_vec
+        let expected: Vec<_> = (0..8).map(|x| (Color::new(x * 36, 0, 255 - x * 36), None)).collect();
+        assert_eq!(colors, expected);
     }
 
-    fn terraria_type(value: u32) -> Vec<u8>
+    fn config_file(name: &str, text: &str) -> String
     {
-        let length_mod = value%128;
-        let mut full_msg = vec![length_mod as u8];
-        if value>127
-        {
-            full_msg[0] += 128;
-            let divisions = value/128_u32;
+        let path = env::temp_dir().join(format!("terraria_cool_colorer_{name}.toml"));
+        fs::write(&path, text).unwrap();
 
-            let mult = divisions as u8;
+        path.to_string_lossy().into_owned()
+    }
 
-            full_msg.push(mult);
+    fn preview_settings(args: &[&str]) -> (Vec<Color>, ColorerSettings)
+    {
+        match parse(args).unwrap().command
+        {
+            Command::Preview(preview) => (preview.colors, preview.colorer),
+            _ => panic!("not a preview")
         }
-        full_msg
     }
-}
 
-impl<'a> StreamReader for ClientReader<'a>
-{
-    fn read_stream(&mut self) -> &mut TcpStream
+    #[test]
+    fn config_shift_means_shifting()
     {
-        self.read_stream
+        let on = config_file("shift_on", "shift = true");
+        let off = config_file("shift_off", "shift = false");
+
+        assert!(preview_settings(&["preview", "--config", &on]).1.shift);
+        assert!(!preview_settings(&["preview", "--config", &off]).1.shift);
+        assert!(preview_settings(&["preview", "--config", &off, "--unset", "shift"]).1.shift);
     }
 
-    fn handle_buffer(&mut self, buffer: &[u8]) -> Vec<u8>
+    #[test]
+    fn command_line_wins_over_config()
     {
-        let size = buffer.len();
-        if size>=Self::MINIMUM_SIZE && buffer[2..9]==Self::CHAT_MESSAGE_HEADER
-        {
-            self.change_chat(buffer)
-        } else
+        let path = config_file("precedence", "colors = [\"red\", \"blue\"]\nclamp = true\nrepeat = 2");
+
+        let (colors, settings) = preview_settings(&["preview", "--config", &path]);
+        assert_eq!(colors, vec![Color::new(255, 0, 0), Color::new(0, 0, 255)]);
+        assert_eq!(settings.repeat, 2.0);
+
+        assert_eq!(preview_settings(&["preview", "--config", &path, "-r", "3"]).1.repeat, 3.0);
+
+        //picking colors on the command line replaces the files colors
+        assert_eq!(preview_settings(&["preview", "--config", &path, "--rainbow"]).0.len(), 6);
+        assert_eq!(preview_settings(&["preview", "--config", &path, "--solid", "0,255,0"]).0, vec![Color::new(0, 255, 0)]);
+
+        assert_eq!(preview_settings(&["preview", "--config", &path, "-C", "300,0,0"]).0, vec![Color::new(255, 0, 0)]);
+        assert!(parse(&["preview", "--config", &path, "--unset", "clamp", "-C", "300,0,0"]).is_err());
+    }
+
+    #[test]
+    fn config_rejects_unknown_keys()
+    {
+        let path = config_file("unknown", "shift-amount = 0.5\nbogus = 1");
+
+        let err = parse(&["preview", "--config", &path]).err().unwrap();
+        assert!(err.contains("unknown field `bogus`"), "{err}");
+
+        //values in the file are checked the same as on the command line
+        let path = config_file("invalid", "repeat = -1");
+        assert!(parse(&["preview", "--config", &path]).is_err());
+    }
+
+    #[test]
+    fn palette_tag_format()
+    {
+        let (_, settings) = preview_settings(&["preview", "-s", "--tag-format", "palette"]);
+
+        assert_eq!(settings.palette, Some(Color::game_palette()));
+
+        let mut colorer = Colorer::new(vec![Color::new(120, 125, 135), Color::new(0, 0, 255)], settings);
+        assert_eq!(colorer.color_at(0.0), Color::new(130, 130, 130));
+
+        for format in [&["--tag-format", "hex"][..], &[]]
         {
-            buffer.to_vec()
+            let args: Vec<_> = ["preview"].iter().chain(format).copied().collect();
+            assert_eq!(preview_settings(&args).1.palette, None);
         }
+
+        assert!(parse(&["preview", "--tag-format", "names"]).is_err());
+        assert!(parse(&["preview", "--tag-format", "palette", "--palette-quantize", "red"]).is_err());
+
+        //game isnt a color list
+        assert!(parse(&["preview", "--palette-quantize", "game"]).is_err());
     }
-}
 
-impl<'a> ProxyPart<'a> for ClientReader<'a>
-{
-    fn write_stream(&mut self) -> &mut TcpStream
+    #[test]
+    fn ports_in_range()
     {
-        self.write_stream
-    }
-}
+        assert_eq!(parse_port("8888").map(NonZeroU16::get), Ok(8888));
+        assert_eq!(parse_port("65535").map(NonZeroU16::get), Ok(65535));
 
+        for port in ["0", "70000"]
+        {
+            let err = parse_port(port).unwrap_err();
+            assert!(err.contains(&format!("between 1 and 65535, got {port}")), "{err}");
+        }
 
-struct ServerReader<'a>
-{
-    read_stream: &'a mut TcpStream,
-    write_stream:  &'a mut TcpStream
-}
+        assert!(parse_port("port").is_err());
+    }
 
-impl<'a> ServerReader<'a>
-{
-    pub fn spawn(read_stream: &'a mut TcpStream, write_stream: &'a mut TcpStream) -> Self
+    #[test]
+    fn warnings_wait_for_the_log_level()
     {
-        ServerReader{
-            read_stream,
-            write_stream
-            }
+        let config = parse(&["preview", "-q", "--solid", "red", "-i", "cubic"]).unwrap();
+
+        assert_eq!(config.log_level, LogLevel::Error);
+        assert_eq!(config.warnings, ["--solid ignores -i, --interpolation, -r and --repeat"]);
+
+        let config = parse(&["preview", "--clamp", "-C", "300,0,0", "--unset", "colors"]).unwrap();
+
+        assert_eq!(config.log_level, LogLevel::Info);
+        assert_eq!(config.warnings.len(), 2, "{:?}", config.warnings);
     }
-}
 
-impl<'a> StreamReader for ServerReader<'a>
-{
-    fn read_stream(&mut self) -> &mut TcpStream
+    #[test]
+    fn stdin_isnt_for_the_proxy()
     {
-        self.read_stream
+        for flag in ["--stdin", "--preview"]
+        {
+            let err = parse(&["proxy", flag, "-c", "127.0.0.1"]).err().unwrap();
+            assert!(err.contains(flag) && err.contains("proxy subcommand"), "{err}");
+
+            //without a subcommand its still the old way of previewing
+            assert!(matches!(parse(&[flag]).unwrap().command, Command::Preview(_)));
+        }
     }
 
-    fn handle_buffer(&mut self, buffer: &[u8]) -> Vec<u8>
+    #[test]
+    fn server_addresses()
     {
-        buffer.to_vec()
+        let cases = [
+            ("example.com", "example.com:7777"),
+            ("127.0.0.1:1234", "127.0.0.1:1234"),
+            ("::1", "[::1]:7777"),
+            ("[::1]", "[::1]:7777"),
+            ("[::1]:1234", "[::1]:1234")
+            ];
+
+        for (address, expected) in cases
+        {
+            assert_eq!(with_server_port(address, 7777).as_deref(), Ok(expected));
+        }
+
+        for address in ["[::1", "[::1]1234", "[nope]:1234", ":1234", "host:0", "host:port", "a b:1234", "host:1:2"]
+        {
+            assert!(with_server_port(address, 7777).is_err(), "{address}");
+        }
+
+        assert!(parse(&["proxy", "-c", "[::1"]).is_err());
     }
-}
 
-impl<'a> ProxyPart<'a> for ServerReader<'a>
-{
-    fn write_stream(&mut self) -> &mut TcpStream
+    #[test]
+    fn unknown_game_versions_keep_colors()
     {
-        self.write_stream
+        let no_color = |version: &str|
+        {
+            let config = parse(&["proxy", "-c", "127.0.0.1", "--game-version", version]).unwrap();
+
+            let Command::Proxy(proxy) = config.command
+            else
+            {
+                panic!("not the proxy");
+            };
+
+            (proxy.no_color, config.warnings.len())
+        };
+
+        assert_eq!(no_color("1.4.4"), (false, 0));
+        assert_eq!(no_color("1.2.4"), (true, 1));
+
+        for version in ["tModLoader", "1.4.4-beta", ""]
+        {
+            assert_eq!(no_color(version), (false, 1), "{version}");
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,289 @@
+//the type byte of net module packets, chat goes through the text module of those
+pub const NET_MODULE: u8 = 0x52;
+
+//floating text over entities, both have the position as two f32s and then the color,
+//the first one ends with the number shown as an i32 and the second with the text
+pub const COMBAT_TEXT_INT: u8 = 0x51;
+pub const COMBAT_TEXT_STRING: u8 = 0x77;
+
+//sent by the server once the player is in the world, has nothing after the type
+pub const FINISHED_CONNECTING: u8 = 0x81;
+
+enum PendingPacket
+{
+    Incomplete,
+    Matching(usize),
+    Other(usize)
+}
+
+//splits a stream into terraria packets, packets starting with one of the headers get
+//collected until theyre complete, everything else is forwarded as soon as it arrives
+//only the length and type of other packets are ever read, so compressed ones like tile
+//sections (0x0a) go through by their length and their payload is never mistaken for chat
+pub struct PacketBuffer
+{
+    headers: Vec<(u8, &'static [u8])>,
+    pending: Vec<u8>,
+    passthrough: usize
+}
+
+impl PacketBuffer
+{
+    //header is whatever comes right after the message type byte
+    pub fn new(message_type: u8, header: &'static [u8]) -> Self
+    {
+        PacketBuffer{headers: vec![(message_type, header)], pending: Vec::new(), passthrough: 0}
+    }
+
+    //with no headers nothing gets collected and everything is forwarded
+    pub fn empty() -> Self
+    {
+        PacketBuffer{headers: Vec::new(), pending: Vec::new(), passthrough: 0}
+    }
+
+    pub fn also(mut self, message_type: u8, header: &'static [u8]) -> Self
+    {
+        self.headers.push((message_type, header));
+
+        self
+    }
+
+    //true when no part of a packet is waiting for the rest of it
+    pub fn is_empty(&self) -> bool
+    {
+        self.pending.is_empty()
+    }
+
+    //whatever on_packet returns takes the place of that packet, everything around it
+    //keeps its order so a buffer with a few packets in it comes out in the same order
+    pub fn handle(&mut self, buffer: &[u8], mut on_packet: impl FnMut(&[u8]) -> Vec<u8>) -> Vec<u8>
+    {
+        self.pending.extend_from_slice(buffer);
+
+        let mut out = Vec::new();
+        while !self.pending.is_empty()
+        {
+            //rest of a packet that doesnt match, forwarded as soon as it arrives
+            if self.passthrough>0
+            {
+                let amount = self.passthrough.min(self.pending.len());
+                out.extend(self.pending.drain(..amount));
+
+                self.passthrough -= amount;
+                continue;
+            }
+
+            match self.pending_packet()
+            {
+                PendingPacket::Incomplete => break,
+                PendingPacket::Matching(length) =>
+                {
+                    let packet: Vec<u8> = self.pending.drain(..length).collect();
+
+                    out.extend(on_packet(&packet));
+                },
+                PendingPacket::Other(length) =>
+                {
+                    self.passthrough = length;
+                }
+            }
+        }
+
+        out
+    }
+
+    fn minimum_size(header: &[u8]) -> usize
+    {
+        //length, message type and header
+        2 + 1 + header.len()
+    }
+
+    fn pending_packet(&self) -> PendingPacket
+    {
+        let pending = &self.pending;
+
+        if pending.len()<2
+        {
+            return PendingPacket::Incomplete;
+        }
+
+        let length = u16::from_le_bytes([pending[0], pending[1]]) as usize;
+        if length<3
+        {
+            //malformed length, theres no way to find where the next packet starts
+            return PendingPacket::Other(pending.len());
+        }
+
+        let Some(&message_type) = pending.get(2)
+        else
+        {
+            return PendingPacket::Incomplete;
+        };
+
+        //the type decides what the packet is, other packets are never touched
+        //even if their bytes happen to look like the header
+        let Some(&(_, expected)) = self.headers.iter().find(|(header_type, _)| *header_type==message_type)
+        else
+        {
+            return PendingPacket::Other(length);
+        };
+
+        let minimum_size = Self::minimum_size(expected);
+        if length<minimum_size
+        {
+            return PendingPacket::Other(length);
+        }
+
+        //only as much of the header as has arrived so far
+        let header = pending.get(3..).unwrap_or_default();
+        let compared = header.len().min(expected.len());
+
+        if header[..compared]!=expected[..compared]
+        {
+            return PendingPacket::Other(length);
+        }
+
+        if pending.len()<minimum_size.max(length)
+        {
+            PendingPacket::Incomplete
+        } else
+        {
+            PendingPacket::Matching(length)
+        }
+    }
+}
+
+//prepends the length of the whole packet
+pub fn with_length(body: Vec<u8>) -> Vec<u8>
+{
+    let payload_length = (body.len() + 2) as u16;
+
+    let mut packet = payload_length.to_le_bytes().to_vec();
+    packet.extend(body);
+
+    packet
+}
+
+//7 bits per byte, highest bit means theres more, so 127 is the longest
+//length that fits in a single byte and 128 already takes two
+pub fn encode_length(mut value: u32) -> Vec<u8>
+{
+    let mut encoded = Vec::new();
+    while value>127
+    {
+        encoded.push((value%128) as u8 | 0x80);
+        value /= 128;
+    }
+
+    encoded.push(value as u8);
+
+    encoded
+}
+
+pub fn decode_length(buffer: &[u8]) -> Option<(u32, usize)>
+{
+    let mut value = 0_u32;
+
+    //a u32 fits in 5 bytes
+    for (index, byte) in buffer.iter().take(5).enumerate()
+    {
+        value |= ((byte & 0x7f) as u32) << (index * 7);
+
+        if byte & 0x80 == 0
+        {
+            return Some((value, index + 1));
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn lengths_round_trip()
+    {
+        for (value, size) in [(0, 1), (127, 1), (128, 2), (16383, 2), (16384, 3), (200000, 3)]
+        {
+            let encoded = encode_length(value);
+
+            assert_eq!(encoded.len(), size);
+            assert_eq!(decode_length(&encoded), Some((value, size)));
+        }
+    }
+
+    #[test]
+    fn unfinished_length()
+    {
+        assert_eq!(decode_length(&[]), None);
+        assert_eq!(decode_length(&[0x80, 0x80]), None);
+    }
+
+    fn packet(message_type: u8, body: &[u8]) -> Vec<u8>
+    {
+        let mut packet = vec![message_type];
+        packet.extend(body);
+
+        with_length(packet)
+    }
+
+    fn marked(packet: &[u8]) -> Vec<u8>
+    {
+        let mut marked = b"<".to_vec();
+        marked.extend(packet);
+        marked.push(b'>');
+
+        marked
+    }
+
+    #[test]
+    fn packets_keep_their_order()
+    {
+        let chat = packet(NET_MODULE, &[1, 0, 3, b'S', b'a', b'y', 2, b'h', b'i']);
+        let other = packet(0x07, &[9; 20]);
+        let other_module = packet(NET_MODULE, &[2, 0, 5, 5]);
+        let looks_like_chat = packet(0x0a, &[NET_MODULE, 1, 0, 3]);
+
+        let table = [
+            vec![&other, &chat, &other],
+            vec![&chat, &chat],
+            vec![&other, &looks_like_chat, &chat],
+            vec![&chat, &other_module, &chat, &other],
+            vec![&looks_like_chat, &other_module, &other]
+            ];
+
+        for packets in table
+        {
+            let stream: Vec<u8> = packets.iter().flat_map(|packet| packet.iter().copied()).collect();
+
+            let expected: Vec<u8> = packets.iter().flat_map(|packet|
+            {
+                if **packet==chat
+                {
+                    marked(packet)
+                } else
+                {
+                    packet.to_vec()
+                }
+            }).collect();
+
+            //in one read, split in two at every point and one byte at a time
+            let mut splits: Vec<Vec<&[u8]>> = vec![vec![&stream]];
+            splits.extend((1..stream.len()).map(|split| vec![&stream[..split], &stream[split..]]));
+            splits.push(stream.chunks(1).collect());
+
+            for reads in splits
+            {
+                let mut buffer = PacketBuffer::new(NET_MODULE, &[1, 0]);
+
+                let out: Vec<u8> = reads.into_iter().flat_map(|read| buffer.handle(read, marked)).collect();
+
+                assert_eq!(out, expected);
+                assert!(buffer.is_empty());
+            }
+        }
+    }
+}
@@ -0,0 +1,1177 @@
+use std::thread::{self, JoinHandle, ScopedJoinHandle};
+
+use std::time::{Duration, Instant};
+
+use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+
+use std::io::{self, Write, BufReader, BufRead, ErrorKind};
+use std::net::{IpAddr, SocketAddr, Shutdown, TcpStream, TcpListener};
+
+use crate::{
+    log::{log_error, log_info, log_debug},
+    packet::{self, PacketBuffer},
+    dump::{PacketDump, Direction},
+    socks5,
+    resolve::Resolver,
+    stats,
+    colorer::{Colorer, ColorerSettings, Color}
+};
+
+
+//the packet length is a u16 and the biggest chat packet header around the text is 13 bytes
+pub const MAX_MESSAGE_LENGTH: usize = u16::MAX as usize - 13;
+
+//how often blocked reads and accepts wake up to check for a shutdown
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+//a server that drops the connection attempts instead of refusing them shouldnt hold up
+//the retries and backup addresses for the minutes it takes the os to give up
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+//how many times in a row a read can fail for an unknown reason before the session ends
+const READ_RETRIES: u32 = 3;
+
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+pub fn shutdown()
+{
+    SHUTDOWN.store(true, Ordering::Relaxed);
+}
+
+fn shutting_down() -> bool
+{
+    SHUTDOWN.load(Ordering::Relaxed)
+}
+
+pub struct ProxyConfig
+{
+    pub connect_addresses: Vec<String>,
+    pub listen_address: IpAddr,
+    pub allow_remote_clients: bool,
+    pub port: u16,
+    pub once: bool,
+    pub timeout: Option<Duration>,
+    pub color_incoming: bool,
+    pub color_system: bool,
+    pub color_combat_text: bool,
+    pub echo: bool,
+    pub max_length: usize,
+    pub dump: Option<String>,
+    pub socks5: Option<String>,
+    pub max_connections: Option<usize>,
+    pub no_color: bool,
+    pub nodelay: bool,
+    pub profile: bool,
+    pub banner: Option<String>,
+    pub colors: Vec<Color>,
+    pub colorer: ColorerSettings
+}
+
+//whats shared between the two halves of a session
+struct Session
+{
+    peer: SocketAddr,
+    dump: Option<Arc<PacketDump>>,
+    closed: AtomicBool
+}
+
+pub fn run_proxy(config: &ProxyConfig) -> Result<(), String>
+{
+    //anyone who can reach the address could send chat as the player
+    if !config.listen_address.is_loopback() && !config.allow_remote_clients
+    {
+        return Err(format!(
+            "{} lets other devices connect to the proxy, pass --allow-remote-clients if thats wanted",
+            config.listen_address
+            ));
+    }
+
+    //SocketAddr adds the brackets around ipv6 addresses
+    let listen_address = SocketAddr::new(config.listen_address, config.port);
+
+    let listener = TcpListener::bind(listen_address)
+        .map_err(|err| format!("could not start a local server on {listen_address}: {err}"))?;
+
+    serve_proxy(listener, config)
+}
+
+//runs the proxy on an already bound listener, binding to port 0 and asking the listener
+//for its address lets callers pick a free port
+pub fn serve_proxy(listener: TcpListener, config: &ProxyConfig) -> Result<(), String>
+{
+    let listen_address = listener.local_addr()
+        .map_err(|err| format!("could not get the local server address: {err}"))?;
+
+    let dump = config.dump.as_deref().map(PacketDump::create).transpose()?.map(Arc::new);
+
+    let resolver = Resolver::new();
+
+    stats::set_profile(config.profile);
+
+    //nonblocking so the loop can notice a shutdown between connections
+    listener.set_nonblocking(true)
+        .map_err(|err| format!("could not configure the local server: {err}"))?;
+
+    log_info!("listening for incoming connections: {listen_address}");
+
+    //sessions connect to the server on their own thread so a slow or missing
+    //server doesnt hold up accepting other clients or noticing a shutdown
+    let result = thread::scope(|scope|
+    {
+        let resolver = &resolver;
+
+        let mut sessions = Vec::new();
+
+        //an error still waits for the sessions that are already going
+        let mut result = Ok(());
+
+        while !shutting_down()
+        {
+            let (stream, peer) = match listener.accept()
+            {
+                Ok(accepted) => accepted,
+                Err(err) if err.kind()==ErrorKind::WouldBlock =>
+                {
+                    thread::sleep(POLL_INTERVAL);
+                    continue;
+                },
+                Err(err) =>
+                {
+                    result = Err(format!("could not establish connection: {err}"));
+                    break;
+                }
+            };
+
+            log_info!("connection from {peer}");
+
+            //some platforms pass the nonblocking flag on to accepted streams
+            if let Err(err) = stream.set_nonblocking(false)
+            {
+                result = Err(format!("could not configure connection: {err}"));
+                break;
+            }
+
+            sessions.retain(|session: &ScopedJoinHandle<()>| !session.is_finished());
+
+            if config.max_connections.is_some_and(|max_connections| sessions.len()>=max_connections)
+            {
+                log_info!("connection limit of {} reached, rejecting {peer}", sessions.len());
+
+                let _ = stream.shutdown(Shutdown::Both);
+                continue;
+            }
+
+            let dump = dump.clone();
+            sessions.push(scope.spawn(move || run_session(config, stream, peer, dump, resolver)));
+
+            if config.once
+            {
+                break;
+            }
+        }
+
+        if shutting_down()
+        {
+            log_info!("shutting down");
+        }
+
+        //lets sessions finish the packet theyre on instead of cutting them off mid write
+        for session in sessions
+        {
+            let _ = session.join();
+        }
+
+        result
+    });
+
+    stats::print_stats();
+
+    result
+}
+
+//a failed session shouldnt take down the whole proxy
+fn run_session(
+    config: &ProxyConfig,
+    stream: TcpStream,
+    peer: SocketAddr,
+    dump: Option<Arc<PacketDump>>,
+    resolver: &Resolver
+    )
+{
+    match start_session(config, stream, peer, dump, resolver)
+    {
+        Ok(handles) =>
+        {
+            stats::record_connection();
+
+            //a session is over once both of its halves are
+            for handle in handles
+            {
+                let _ = handle.join();
+            }
+        },
+        //the proxy is going away anyway so theres nothing to report
+        Err(_) if shutting_down() => (),
+        Err(err) => log_error!("error starting session: {err}")
+    }
+}
+
+fn start_session(
+    config: &ProxyConfig,
+    mut write_stream: TcpStream,
+    peer: SocketAddr,
+    dump: Option<Arc<PacketDump>>,
+    resolver: &Resolver
+    ) -> Result<[JoinHandle<()>; 2], String>
+{
+    let mut write_connector = connect_upstream(&config.connect_addresses, config.socks5.as_deref(), resolver)?;
+
+    //chat packets are tiny so waiting to batch them up only adds lag
+    if config.nodelay
+    {
+        for stream in [&write_stream, &write_connector]
+        {
+            if let Err(err) = stream.set_nodelay(true)
+            {
+                log_error!("error disabling nagles algorithm: {err}");
+            }
+        }
+    }
+
+    let mut read_stream = write_stream.try_clone()
+        .map_err(|err| format!("error cloning client stream: {err}"))?;
+    let mut read_connector = write_connector.try_clone()
+        .map_err(|err| format!("error cloning server stream: {err}"))?;
+
+    let colorer = Colorer::new(config.colors.clone(), config.colorer.clone());
+
+    let color_chat = config.color_incoming && !config.no_color;
+    let color_combat_text = config.color_combat_text && !config.no_color;
+
+    let server_colorer = (color_chat || color_combat_text).then(||
+    {
+        Colorer::new(config.colors.clone(), config.colorer.clone())
+    });
+
+    let banner = config.banner.as_deref().map(|banner|
+    {
+        if config.no_color
+        {
+            banner.to_string()
+        } else
+        {
+            let mut colorer = Colorer::new(config.colors.clone(), config.colorer.clone());
+
+            limit_length(colorer.color_text(banner), banner, config.max_length)
+        }
+    });
+
+    let timeout = config.timeout;
+    let color_system = config.color_system;
+    let echo = config.echo;
+    let no_color = config.no_color;
+
+    if no_color
+    {
+        log_info!("coloring is off, chat is forwarded unchanged");
+    }
+    let max_length = config.max_length;
+
+    let session = Arc::new(Session{peer, dump, closed: AtomicBool::new(false)});
+    let server_session = session.clone();
+
+    let client_handle = thread::spawn(move ||
+    {
+        let result = ClientReader::spawn(&mut read_stream, &mut write_connector, colorer, echo, no_color, max_length, session)
+            .listen_connection(timeout);
+
+        if let Err(err) = result
+        {
+            log_error!("{err}");
+        }
+    });
+
+    let server_handle = thread::spawn(move ||
+    {
+        let result = ServerReader::spawn(
+            &mut read_connector,
+            &mut write_stream,
+            server_colorer,
+            color_chat,
+            color_combat_text,
+            max_length,
+            server_session
+            ).with_banner(banner).with_color_system(color_system).listen_connection(timeout);
+
+        if let Err(err) = result
+        {
+            log_error!("{err}");
+        }
+    });
+
+    Ok([client_handle, server_handle])
+}
+
+fn connect_upstream(
+    addresses: &[String],
+    socks5: Option<&str>,
+    resolver: &Resolver
+    ) -> Result<TcpStream, String>
+{
+    const RETRIES: u32 = 3;
+
+    let mut delay = Duration::from_millis(500);
+
+    let mut attempt = 0;
+    loop
+    {
+        let mut errors = Vec::new();
+
+        //servers are tried in the order they were given
+        for address in addresses
+        {
+            match connect_address(address, socks5, resolver)
+            {
+                Ok(stream) =>
+                {
+                    log_info!("connected to {address}");
+
+                    return Ok(stream);
+                },
+                Err(err) => errors.push(err)
+            }
+        }
+
+        let errors = errors.join(", ");
+
+        if attempt>=RETRIES
+        {
+            return Err(errors);
+        }
+
+        log_info!("{errors}, retrying in {delay:?}");
+
+        //in small steps so a shutdown doesnt have to wait for the whole delay
+        let retry_at = Instant::now() + delay;
+        while Instant::now()<retry_at
+        {
+            if shutting_down()
+            {
+                return Err("shutting down".to_string());
+            }
+
+            thread::sleep(POLL_INTERVAL);
+        }
+
+        delay *= 2;
+
+        attempt += 1;
+    }
+}
+
+//lengths are in bytes, a message that doesnt fit in the packet or isnt valid utf8
+//gets forwarded as it is instead of being mangled by a lossy conversion
+fn read_message(buffer: &[u8], start: usize, length: u32) -> Option<&str>
+{
+    let bytes = buffer.get(start..start+length as usize)?;
+
+    std::str::from_utf8(bytes).ok()
+}
+
+fn color_message(colorer: &mut Colorer, message: &str, max_length: usize) -> String
+{
+    //only timed when asked for so theres no extra work otherwise
+    let start = stats::profiling().then(Instant::now);
+
+    let colored = colorer.color_text(message);
+
+    if let Some(start) = start
+    {
+        let time = start.elapsed();
+        let average = stats::record_coloring(time);
+
+        log_debug!("coloring took {}us, {average}us on average", time.as_micros());
+    }
+
+    let new_message = limit_length(colored, message, max_length);
+    stats::record_message(message.len(), new_message.len());
+
+    new_message
+}
+
+fn limit_length(colored: String, message: &str, max_length: usize) -> String
+{
+    if colored.len()<=max_length
+    {
+        return colored;
+    }
+
+    //an uncolored message is better than the server dropping it
+    log_error!(
+        "colored message is {} bytes which is over the {max_length} byte limit, sending it uncolored",
+        colored.len()
+        );
+
+    let mut end = message.len().min(max_length);
+    while !message.is_char_boundary(end)
+    {
+        end -= 1;
+    }
+
+    message[..end].to_string()
+}
+
+fn connect_address(address: &str, socks5: Option<&str>, resolver: &Resolver) -> Result<TcpStream, String>
+{
+    //the proxy resolves the address itself
+    if let Some(proxy) = socks5
+    {
+        return socks5::connect(proxy, address, CONNECT_TIMEOUT);
+    }
+
+    //resolving first tells a missing host apart from a refused connection
+    let resolved = resolver.resolve(address)?;
+
+    let mut errors = Vec::new();
+    for socket_address in &resolved
+    {
+        match TcpStream::connect_timeout(socket_address, CONNECT_TIMEOUT)
+        {
+            Ok(stream) => return Ok(stream),
+            Err(err) => errors.push(format!("{socket_address}: {err}"))
+        }
+    }
+
+    //the host might have moved so the next try looks it up again
+    resolver.forget(address);
+
+    Err(format!("could not connect to {address} (tried {})", errors.join(", ")))
+}
+
+
+trait StreamReader
+{
+    const DIRECTION: Direction;
+
+    fn read_stream(&mut self) -> &mut TcpStream;
+
+    fn session(&self) -> &Session;
+
+    fn dump(&self) -> Option<&PacketDump>
+    {
+        self.session().dump.as_deref()
+    }
+
+    //none once the other side closed the connection
+    fn handle_stream(
+        &mut self,
+        ) -> io::Result<Option<Vec<u8>>>
+    {
+        let mut reader = BufReader::new(self.read_stream());
+
+        let buffer: Vec<u8> = reader.fill_buf()?.to_vec();
+
+        if buffer.is_empty()
+        {
+            return Ok(None);
+        }
+
+        stats::record_bytes(Self::DIRECTION, buffer.len());
+
+        //the bytes as they came in, before any coloring
+        if let Some(dump) = self.dump()
+        {
+            dump.record(Self::DIRECTION, &buffer);
+        }
+
+        Ok(Some(self.handle_buffer(&buffer)))
+    }
+
+    fn handle_buffer(&mut self, buffer: &[u8]) -> Vec<u8>;
+}
+
+trait ProxyPart<'a>: StreamReader
+{
+    fn write_stream(&mut self) -> &mut TcpStream;
+
+    fn listen_connection(&mut self, timeout: Option<Duration>) -> Result<(), String>
+    {
+        //reads wake up regularly to check for a shutdown, so the idle timeout is tracked here
+        //only fails for a zero duration which these never are
+        let _ = self.read_stream().set_read_timeout(Some(POLL_INTERVAL));
+        let _ = self.write_stream().set_write_timeout(timeout);
+
+        let mut last_data = Instant::now();
+
+        //errors that arent about the connection being gone get a few tries before giving up
+        let mut failures = 0;
+
+        //the flag is only checked between writes so a packet never gets cut in half
+        let result = loop
+        {
+            if shutting_down()
+            {
+                break Ok(());
+            }
+
+            match self.handle_stream()
+            {
+                Ok(None) =>
+                {
+                    self.log_end("connection closed");
+
+                    break Ok(());
+                },
+                Ok(Some(data)) =>
+                {
+                    last_data = Instant::now();
+                    failures = 0;
+
+                    //a busy socket can take only part of the buffer so keep going until its all out
+                    let written = self.write_stream().write_all(&data)
+                        .and_then(|_| self.write_stream().flush());
+
+                    if let Err(err) = written
+                    {
+                        match err.kind()
+                        {
+                            ErrorKind::BrokenPipe
+                                | ErrorKind::ConnectionReset
+                                | ErrorKind::ConnectionAborted => self.log_end("connection closed"),
+                            ErrorKind::WouldBlock | ErrorKind::TimedOut => self.log_end("connection timed out"),
+                            _ => break Err(format!("error writing to out: {err}"))
+                        }
+
+                        break Ok(());
+                    }
+                },
+                Err(err) =>
+                {
+                    match err.kind()
+                    {
+                        ErrorKind::WouldBlock | ErrorKind::TimedOut =>
+                        {
+                            if timeout.is_none_or(|timeout| last_data.elapsed()<timeout)
+                            {
+                                continue;
+                            }
+
+                            self.log_end("connection timed out");
+                        },
+                        ErrorKind::Interrupted => continue,
+                        ErrorKind::UnexpectedEof
+                            | ErrorKind::ConnectionReset
+                            | ErrorKind::ConnectionAborted => self.log_end("connection closed"),
+                        _ if failures<READ_RETRIES =>
+                        {
+                            failures += 1;
+
+                            log_debug!("error reading in data: {err}, retrying");
+
+                            //backs off so a broken socket doesnt spin the cpu
+                            thread::sleep(POLL_INTERVAL * failures);
+                            continue;
+                        },
+                        _ => break Err(format!("error reading in data: {err}"))
+                    }
+
+                    break Ok(());
+                }
+            }
+        };
+
+        self.close();
+
+        result
+    }
+
+    //both halves end together, only the first one to notice says why
+    fn log_end(&self, reason: &str)
+    {
+        if !self.session().closed.swap(true, Ordering::Relaxed)
+        {
+            log_info!("{reason} ({})", self.session().peer);
+        }
+    }
+
+    fn close(&mut self)
+    {
+        //the other half ends because of this one so it has nothing to add
+        self.session().closed.store(true, Ordering::Relaxed);
+
+        if let Some(dump) = self.dump()
+        {
+            dump.flush();
+        }
+
+        //unblocks the other half of this session so it ends too
+        let _ = self.read_stream().shutdown(Shutdown::Both);
+        let _ = self.write_stream().shutdown(Shutdown::Both);
+    }
+}
+
+
+struct ClientReader<'a>
+{
+    read_stream: &'a mut TcpStream,
+    write_stream: &'a mut TcpStream,
+    colorer: Colorer,
+    echo: bool,
+    no_color: bool,
+    max_length: usize,
+    session: Arc<Session>,
+    packets: PacketBuffer
+}
+
+impl<'a> ClientReader<'a>
+{
+    pub fn spawn(
+        read_stream: &'a mut TcpStream,
+        write_stream: &'a mut TcpStream,
+        colorer: Colorer,
+        echo: bool,
+        no_color: bool,
+        max_length: usize,
+        session: Arc<Session>
+        ) -> Self
+    {
+        ClientReader{
+            read_stream,
+            write_stream,
+            colorer,
+            echo,
+            no_color,
+            max_length,
+            session,
+            packets: PacketBuffer::new(packet::NET_MODULE, &Self::TEXT_MODULE)
+            }
+    }
+
+    //after the module id theres the chat command like Say, Emote or Party and then the text
+    const TEXT_MODULE: [u8; 2] = [0x01, 0x00];
+
+    const COMMAND_POS: usize = 5;
+
+    //only plain chat gets colored, the other commands are forwarded as they are
+    const SAY_COMMAND: &'static [u8] = b"Say";
+
+    fn change_chat(colorer: &mut Colorer, echo: bool, max_length: usize, buffer: &[u8]) -> Vec<u8>
+    {
+        let Some((command_length, length_length)) = packet::decode_length(&buffer[Self::COMMAND_POS..])
+        else
+        {
+            return buffer.to_vec();
+        };
+
+        let command_pos = Self::COMMAND_POS+length_length;
+        let message_pos = command_pos+command_length as usize;
+
+        if buffer.get(command_pos..message_pos)!=Some(Self::SAY_COMMAND)
+        {
+            return buffer.to_vec();
+        }
+
+        let Some((message_length, length_length)) = packet::decode_length(&buffer[message_pos..])
+        else
+        {
+            return buffer.to_vec();
+        };
+
+        let real_msg_pos = message_pos+length_length;
+
+        let Some(message) = read_message(buffer, real_msg_pos, message_length)
+        else
+        {
+            return buffer.to_vec();
+        };
+
+        //private messages go through here too so printing them is opt in,
+        //but once asked for it shows up even with -q
+        if echo
+        {
+            println!("client sent: {message}");
+        }
+
+        let new_message = color_message(colorer, message, max_length);
+
+        log_debug!("recolored a chat message from {} to {} bytes", message.len(), new_message.len());
+
+        //the header and the command
+        let mut body = buffer[2..message_pos].to_vec();
+
+        //length of the message
+        body.extend(packet::encode_length(new_message.len() as u32));
+
+        //message
+        body.extend(new_message.bytes());
+
+        packet::with_length(body)
+    }
+}
+
+impl<'a> StreamReader for ClientReader<'a>
+{
+    const DIRECTION: Direction = Direction::Client;
+
+    fn read_stream(&mut self) -> &mut TcpStream
+    {
+        self.read_stream
+    }
+
+    fn session(&self) -> &Session
+    {
+        &self.session
+    }
+
+    fn handle_buffer(&mut self, buffer: &[u8]) -> Vec<u8>
+    {
+        //for checking if a problem comes from the coloring or from somewhere else
+        if self.no_color
+        {
+            return buffer.to_vec();
+        }
+
+        //chat packets can get split across reads so they wait in the packet buffer until complete
+        let colorer = &mut self.colorer;
+        let (echo, max_length) = (self.echo, self.max_length);
+        self.packets.handle(buffer, |packet| Self::change_chat(colorer, echo, max_length, packet))
+    }
+}
+
+impl<'a> ProxyPart<'a> for ClientReader<'a>
+{
+    fn write_stream(&mut self) -> &mut TcpStream
+    {
+        self.write_stream
+    }
+}
+
+
+struct ServerReader<'a>
+{
+    read_stream: &'a mut TcpStream,
+    write_stream:  &'a mut TcpStream,
+    colorer: Option<Colorer>,
+    max_length: usize,
+    color_system: bool,
+    banner: Option<Vec<u8>>,
+    session: Arc<Session>,
+    packets: PacketBuffer
+}
+
+impl<'a> ServerReader<'a>
+{
+    pub fn spawn(
+        read_stream: &'a mut TcpStream,
+        write_stream: &'a mut TcpStream,
+        colorer: Option<Colorer>,
+        color_chat: bool,
+        color_combat_text: bool,
+        max_length: usize,
+        session: Arc<Session>
+        ) -> Self
+    {
+        let mut packets = PacketBuffer::empty();
+
+        if color_chat
+        {
+            packets = packets.also(packet::NET_MODULE, &Self::TEXT_MODULE);
+        }
+
+        if color_combat_text
+        {
+            packets = packets.also(packet::COMBAT_TEXT_INT, &[])
+                .also(packet::COMBAT_TEXT_STRING, &[]);
+        }
+
+        ServerReader{
+            read_stream,
+            write_stream,
+            colorer,
+            max_length,
+            color_system: false,
+            banner: None,
+            session,
+            packets
+            }
+    }
+
+    //the client ignores chat until its in the world, so the banner waits for the server to say it is
+    pub fn with_banner(mut self, banner: Option<String>) -> Self
+    {
+        if let Some(banner) = banner
+        {
+            self.packets = self.packets.also(packet::FINISHED_CONNECTING, &[]);
+            self.banner = Some(Self::chat_packet(&banner));
+        }
+
+        self
+    }
+
+    //join and leave notices and announcements are left alone unless asked for
+    pub fn with_color_system(mut self, color_system: bool) -> Self
+    {
+        self.color_system = color_system;
+
+        self
+    }
+
+    //a message with no author like the ones the server sends itself
+    fn chat_packet(message: &str) -> Vec<u8>
+    {
+        let mut body = vec![packet::NET_MODULE];
+        body.extend(Self::TEXT_MODULE);
+        body.extend([Self::NO_AUTHOR, 0]);
+
+        body.extend(packet::encode_length(message.len() as u32));
+        body.extend(message.bytes());
+
+        //the tags override this anyway
+        body.extend([255, 255, 255]);
+
+        packet::with_length(body)
+    }
+
+    //the server doesnt send a command, after the module id theres the author
+    //and then the text itself
+    const TEXT_MODULE: [u8; 2] = [0x01, 0x00];
+
+    const AUTHOR_POS: usize = 5;
+    const TEXT_MODE_POS: usize = 6;
+
+    //the server itself has no player id
+    const NO_AUTHOR: u8 = 255;
+    const MESSAGE_POS: usize = 7;
+
+    //right after the x and y position
+    const COMBAT_COLOR_POS: usize = 11;
+
+    //every piece of combat text gets its own color from somewhere along the gradient
+    fn change_combat_text(colorer: &mut Colorer, buffer: &[u8]) -> Vec<u8>
+    {
+        let mut packet = buffer.to_vec();
+
+        if let Some(color) = packet.get_mut(Self::COMBAT_COLOR_POS..Self::COMBAT_COLOR_POS+3)
+        {
+            let new_color = colorer.random_color();
+
+            color.copy_from_slice(&[new_color.r(), new_color.g(), new_color.b()]);
+        }
+
+        packet
+    }
+
+    fn change_chat(colorer: &mut Colorer, max_length: usize, color_system: bool, buffer: &[u8]) -> Vec<u8>
+    {
+        if !color_system && buffer.get(Self::AUTHOR_POS)==Some(&Self::NO_AUTHOR)
+        {
+            return buffer.to_vec();
+        }
+
+        //only literal text gets colored, other modes hold localization keys
+        if buffer.get(Self::TEXT_MODE_POS)!=Some(&0)
+        {
+            return buffer.to_vec();
+        }
+
+        let Some((message_length, length_length)) = packet::decode_length(&buffer[Self::MESSAGE_POS..])
+        else
+        {
+            return buffer.to_vec();
+        };
+
+        let real_msg_pos = Self::MESSAGE_POS+length_length;
+        let message_end = real_msg_pos+message_length as usize;
+
+        //the author is sent as a player id so the text is only the message body
+        let Some(message) = read_message(buffer, real_msg_pos, message_length)
+        else
+        {
+            return buffer.to_vec();
+        };
+
+        let new_message = color_message(colorer, message, max_length);
+
+        //header, author and text mode
+        let mut body = buffer[2..Self::MESSAGE_POS].to_vec();
+
+        body.extend(packet::encode_length(new_message.len() as u32));
+        body.extend(new_message.bytes());
+
+        //color of the message
+        body.extend(&buffer[message_end..]);
+
+        packet::with_length(body)
+    }
+}
+
+impl<'a> StreamReader for ServerReader<'a>
+{
+    const DIRECTION: Direction = Direction::Server;
+
+    fn read_stream(&mut self) -> &mut TcpStream
+    {
+        self.read_stream
+    }
+
+    fn session(&self) -> &Session
+    {
+        &self.session
+    }
+
+    fn handle_buffer(&mut self, buffer: &[u8]) -> Vec<u8>
+    {
+        //once theres nothing left to change the packets dont need to be looked at, unless
+        //the start of one is still waiting in the packet buffer from the last read
+        if self.colorer.is_none() && self.banner.is_none() && self.packets.is_empty()
+        {
+            return buffer.to_vec();
+        }
+
+        let (colorer, banner) = (&mut self.colorer, &mut self.banner);
+        let (max_length, color_system) = (self.max_length, self.color_system);
+        self.packets.handle(buffer, |packet|
+        {
+            match (packet[2], colorer.as_mut())
+            {
+                (packet::FINISHED_CONNECTING, _) =>
+                {
+                    //right after so the client is already listening for chat
+                    let mut packet = packet.to_vec();
+                    packet.extend(banner.take().unwrap_or_default());
+
+                    packet
+                },
+                (packet::NET_MODULE, Some(colorer)) => Self::change_chat(colorer, max_length, color_system, packet),
+                (_, Some(colorer)) => Self::change_combat_text(colorer, packet),
+                _ => packet.to_vec()
+            }
+        })
+    }
+}
+
+impl<'a> ProxyPart<'a> for ServerReader<'a>
+{
+    fn write_stream(&mut self) -> &mut TcpStream
+    {
+        self.write_stream
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::colorer::{Color, ColorerSettings};
+
+    fn red() -> Colorer
+    {
+        Colorer::new(vec![Color::new(255, 0, 0)], ColorerSettings{shift: false, ..Default::default()})
+    }
+
+    fn chat(command: &str, message: &str) -> Vec<u8>
+    {
+        let mut body = vec![packet::NET_MODULE];
+        body.extend(ClientReader::TEXT_MODULE);
+
+        body.extend(packet::encode_length(command.len() as u32));
+        body.extend(command.bytes());
+
+        body.extend(packet::encode_length(message.len() as u32));
+        body.extend(message.bytes());
+
+        packet::with_length(body)
+    }
+
+    fn say(message: &str) -> Vec<u8>
+    {
+        chat("Say", message)
+    }
+
+    fn client_packets() -> PacketBuffer
+    {
+        PacketBuffer::new(packet::NET_MODULE, &ClientReader::TEXT_MODULE)
+    }
+
+    fn handle_client(packets: &mut PacketBuffer, colorer: &mut Colorer, buffer: &[u8]) -> Vec<u8>
+    {
+        packets.handle(buffer, |packet|
+        {
+            ClientReader::change_chat(colorer, false, MAX_MESSAGE_LENGTH, packet)
+        })
+    }
+
+    #[test]
+    fn chat_split_into_single_bytes()
+    {
+        let packet = say("hello there");
+        let expected = handle_client(&mut client_packets(), &mut red(), &packet);
+
+        assert!(String::from_utf8_lossy(&expected).contains("[c/ff0000:"));
+
+        let mut packets = client_packets();
+        let mut colorer = red();
+
+        let (last, start) = packet.split_last().unwrap();
+        for byte in start
+        {
+            assert!(handle_client(&mut packets, &mut colorer, &[*byte]).is_empty());
+        }
+
+        assert_eq!(handle_client(&mut packets, &mut colorer, &[*last]), expected);
+    }
+
+    #[test]
+    fn other_packets_dont_wait()
+    {
+        let mut packets = client_packets();
+        let mut colorer = red();
+
+        //a tile section that hasnt fully arrived yet
+        let start = [0x10, 0x00, 0x0a, 1, 2, 3];
+
+        assert_eq!(handle_client(&mut packets, &mut colorer, &start), start);
+        assert_eq!(handle_client(&mut packets, &mut colorer, &[4; 10]), [4; 10]);
+    }
+
+    #[test]
+    fn chat_followed_by_another_packet()
+    {
+        let chat = say("hi");
+        let other = [0x07, 0x00, 0x0d, 9, 8, 7, 6];
+
+        let colored = handle_client(&mut client_packets(), &mut red(), &chat);
+        assert_ne!(colored, chat);
+
+        let mut buffer = chat.clone();
+        buffer.extend(other);
+
+        let mut expected = colored;
+        expected.extend(other);
+
+        assert_eq!(handle_client(&mut client_packets(), &mut red(), &buffer), expected);
+    }
+
+    #[test]
+    fn only_say_gets_colored()
+    {
+        let colored = ClientReader::change_chat(&mut red(), false, MAX_MESSAGE_LENGTH, &say("waves"));
+        assert_eq!(colored, say("[c/ff0000:waves]"));
+
+        for command in ["Emote", "Party"]
+        {
+            let packet = chat(command, "waves");
+
+            assert_eq!(ClientReader::change_chat(&mut red(), false, MAX_MESSAGE_LENGTH, &packet), packet);
+        }
+    }
+
+    #[test]
+    fn world_info_passes_through()
+    {
+        //world info with a payload that happens to look like a chat packet
+        let mut body = vec![0x07];
+        body.extend(&say("hi")[2..]);
+        body.extend([0; 40]);
+
+        let packet = packet::with_length(body);
+
+        let mut packets = client_packets();
+        let mut colorer = red();
+
+        assert_eq!(handle_client(&mut packets, &mut colorer, &packet), packet);
+
+        //and the chat right after it still gets colored
+        let colored = handle_client(&mut packets, &mut colorer, &say("hi"));
+        assert_eq!(colored, say("[c/ff0000:hi]"));
+    }
+
+    #[test]
+    fn multi_byte_lengths()
+    {
+        let mut colorer = Colorer::new(
+            vec![Color::new(255, 0, 0), Color::new(0, 0, 255)],
+            ColorerSettings{shift: false, ..Default::default()}
+            );
+
+        let message = "héllo 🌈 ".repeat(3);
+        let colored = ClientReader::change_chat(&mut colorer, false, MAX_MESSAGE_LENGTH, &say(&message));
+
+        assert_eq!(u16::from_le_bytes([colored[0], colored[1]]) as usize, colored.len());
+
+        //header and the Say command
+        let message_pos = 9;
+
+        let (length, length_length) = packet::decode_length(&colored[message_pos..]).unwrap();
+        let text = &colored[message_pos + length_length..];
+
+        //long enough that the length takes two bytes
+        assert_eq!(length_length, 2);
+        assert_eq!(length as usize, text.len());
+
+        let text = std::str::from_utf8(text).unwrap();
+        assert_eq!(text.matches("🌈").count(), 3);
+        assert_eq!(text.matches('é').count(), 3);
+    }
+
+    #[test]
+    fn compressed_section_isnt_chat()
+    {
+        //a compressed tile section is a flag and a raw deflate stream, this one is a single
+        //stored block so the chat header inside it shows up as is in the payload
+        let inside = say("not chat");
+
+        let mut body = vec![0x0a, 1, 0x01];
+        body.extend((inside.len() as u16).to_le_bytes());
+        body.extend((!(inside.len() as u16)).to_le_bytes());
+        body.extend(&inside);
+
+        let section = packet::with_length(body);
+        assert!(section.windows(3).any(|bytes| bytes==[packet::NET_MODULE, 1, 0]));
+
+        let mut sent = section.clone();
+        sent.extend(say("hi"));
+
+        let mut expected = section.clone();
+        expected.extend(say("[c/ff0000:hi]"));
+
+        for split in 0..sent.len()
+        {
+            let mut packets = client_packets();
+            let mut colorer = red();
+
+            let mut received = handle_client(&mut packets, &mut colorer, &sent[..split]);
+            received.extend(handle_client(&mut packets, &mut colorer, &sent[split..]));
+
+            assert_eq!(received, expected);
+        }
+    }
+
+    #[test]
+    fn say_lengths_around_one_byte()
+    {
+        //header and the Say command
+        let message_pos = 9;
+
+        //the tags add 11 bytes so 116 and 117 put the colored message on either side too
+        for (length, sent_size, colored_size) in [(116, 1, 1), (117, 1, 2), (126, 1, 2), (127, 1, 2), (128, 2, 2), (200, 2, 2)]
+        {
+            let message = "a".repeat(length);
+            let sent = say(&message);
+
+            let (sent_length, size) = packet::decode_length(&sent[message_pos..]).unwrap();
+            assert_eq!((sent_length as usize, size), (length, sent_size));
+
+            let colored = ClientReader::change_chat(&mut red(), false, MAX_MESSAGE_LENGTH, &sent);
+            assert_eq!(u16::from_le_bytes([colored[0], colored[1]]) as usize, colored.len());
+
+            let (colored_length, size) = packet::decode_length(&colored[message_pos..]).unwrap();
+            assert_eq!(size, colored_size, "{length}");
+
+            let text = &colored[message_pos + size..];
+            assert_eq!(colored_length as usize, text.len());
+
+            assert_eq!(std::str::from_utf8(text).unwrap(), red().color_text(&message));
+        }
+    }
+}
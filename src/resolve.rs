@@ -0,0 +1,80 @@
+use std::thread;
+
+use std::sync::{Mutex, MutexGuard};
+
+use std::time::Duration;
+
+use std::collections::HashMap;
+
+use std::net::{SocketAddr, ToSocketAddrs};
+
+use crate::log::{log_info, log_debug};
+
+
+//a dns hiccup shouldnt need a whole reconnect to get past
+const RETRIES: u32 = 2;
+const RETRY_DELAY: Duration = Duration::from_millis(250);
+
+//remembers what every address resolved to so new sessions dont wait on dns again,
+//an address gets forgotten when none of its results could be connected to
+pub struct Resolver
+{
+    cache: Mutex<HashMap<String, Vec<SocketAddr>>>
+}
+
+impl Resolver
+{
+    pub fn new() -> Self
+    {
+        Resolver{cache: Mutex::new(HashMap::new())}
+    }
+
+    pub fn resolve(&self, address: &str) -> Result<Vec<SocketAddr>, String>
+    {
+        if let Some(resolved) = self.cache().get(address)
+        {
+            log_debug!("using cached addresses for {address}");
+
+            return Ok(resolved.clone());
+        }
+
+        let mut attempt = 0;
+        let resolved = loop
+        {
+            match address.to_socket_addrs()
+            {
+                Ok(resolved) => break resolved.collect::<Vec<_>>(),
+                Err(err) if attempt>=RETRIES =>
+                {
+                    return Err(format!("could not find host {address}: {err}"));
+                },
+                Err(err) =>
+                {
+                    log_info!("could not find host {address}: {err}, retrying in {RETRY_DELAY:?}");
+
+                    thread::sleep(RETRY_DELAY);
+                    attempt += 1;
+                }
+            }
+        };
+
+        if resolved.is_empty()
+        {
+            return Err(format!("{address} didnt resolve to any addresses"));
+        }
+
+        self.cache().insert(address.to_string(), resolved.clone());
+
+        Ok(resolved)
+    }
+
+    pub fn forget(&self, address: &str)
+    {
+        self.cache().remove(address);
+    }
+
+    fn cache(&self) -> MutexGuard<'_, HashMap<String, Vec<SocketAddr>>>
+    {
+        self.cache.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
@@ -0,0 +1,171 @@
+use std::time::Duration;
+
+use std::io::{Read, Write};
+use std::net::{IpAddr, TcpStream, ToSocketAddrs};
+
+
+const VERSION: u8 = 5;
+
+const NO_AUTHENTICATION: u8 = 0;
+const CONNECT: u8 = 1;
+
+const IPV4: u8 = 1;
+const DOMAIN: u8 = 3;
+const IPV6: u8 = 4;
+
+//a proxy that accepts the connection but never answers shouldnt hang the session forever
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+//connects to the proxy and asks it to connect to the target, only no authentication is supported
+pub fn connect(proxy: &str, target: &str, timeout: Duration) -> Result<TcpStream, String>
+{
+    let (host, port) = split_address(target)?;
+
+    let mut stream = connect_proxy(proxy, timeout)
+        .map_err(|err| format!("could not connect to the socks5 proxy {proxy}: {err}"))?;
+
+    handshake(&mut stream, &host, port)
+        .map_err(|err| format!("socks5 handshake with {proxy} for {target} failed: {err}"))?;
+
+    Ok(stream)
+}
+
+//connect_timeout only takes a single resolved address so every one the proxy has gets tried
+fn connect_proxy(proxy: &str, timeout: Duration) -> Result<TcpStream, String>
+{
+    let mut errors = Vec::new();
+    for address in proxy.to_socket_addrs().map_err(|err| err.to_string())?
+    {
+        match TcpStream::connect_timeout(&address, timeout)
+        {
+            Ok(stream) => return Ok(stream),
+            Err(err) => errors.push(format!("{address}: {err}"))
+        }
+    }
+
+    if errors.is_empty()
+    {
+        return Err("no addresses found".to_string());
+    }
+
+    Err(errors.join(", "))
+}
+
+fn split_address(address: &str) -> Result<(String, u16), String>
+{
+    let (host, port) = address.rsplit_once(':')
+        .ok_or_else(|| format!("{address} has no port"))?;
+
+    let port = port.parse().map_err(|_| format!("{port} is not a valid port in {address}"))?;
+
+    //ipv6 addresses come in brackets
+    let host = host.strip_prefix('[').and_then(|host| host.strip_suffix(']')).unwrap_or(host);
+
+    Ok((host.to_string(), port))
+}
+
+fn handshake(stream: &mut TcpStream, host: &str, port: u16) -> Result<(), String>
+{
+    let _ = stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT));
+
+    write(stream, &[VERSION, 1, NO_AUTHENTICATION])?;
+
+    let [version, method] = read::<2>(stream)?;
+    if version!=VERSION
+    {
+        return Err(format!("proxy answered with version {version}"));
+    }
+
+    if method!=NO_AUTHENTICATION
+    {
+        return Err("proxy requires authentication".to_string());
+    }
+
+    let mut request = vec![VERSION, CONNECT, 0];
+
+    //anything that isnt an ip gets resolved by the proxy
+    match host.parse::<IpAddr>()
+    {
+        Ok(IpAddr::V4(ip)) =>
+        {
+            request.push(IPV4);
+            request.extend(ip.octets());
+        },
+        Ok(IpAddr::V6(ip)) =>
+        {
+            request.push(IPV6);
+            request.extend(ip.octets());
+        },
+        Err(_) =>
+        {
+            let length = u8::try_from(host.len())
+                .map_err(|_| format!("{host} is too long for socks5"))?;
+
+            request.push(DOMAIN);
+            request.push(length);
+            request.extend(host.bytes());
+        }
+    }
+
+    request.extend(port.to_be_bytes());
+
+    write(stream, &request)?;
+
+    let [version, reply, _, address_type] = read::<4>(stream)?;
+    if version!=VERSION
+    {
+        return Err(format!("proxy answered with version {version}"));
+    }
+
+    if reply!=0
+    {
+        return Err(reply_message(reply));
+    }
+
+    //the address the proxy bound to, not needed but it has to be read out of the stream
+    let address_length = match address_type
+    {
+        IPV4 => 4,
+        IPV6 => 16,
+        DOMAIN => read::<1>(stream)?[0] as usize,
+        x => return Err(format!("proxy answered with an unknown address type {x}"))
+    };
+
+    let mut bound = vec![0; address_length + 2];
+    stream.read_exact(&mut bound).map_err(|err| format!("error reading the proxy reply: {err}"))?;
+
+    let _ = stream.set_read_timeout(None);
+
+    Ok(())
+}
+
+fn reply_message(reply: u8) -> String
+{
+    let message = match reply
+    {
+        1 => "general failure",
+        2 => "connection not allowed by ruleset",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "ttl expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown error"
+    };
+
+    format!("proxy replied with {message} ({reply})")
+}
+
+fn write(stream: &mut TcpStream, data: &[u8]) -> Result<(), String>
+{
+    stream.write_all(data).map_err(|err| format!("error writing to the proxy: {err}"))
+}
+
+fn read<const N: usize>(stream: &mut TcpStream) -> Result<[u8; N], String>
+{
+    let mut buffer = [0; N];
+    stream.read_exact(&mut buffer).map_err(|err| format!("error reading the proxy reply: {err}"))?;
+
+    Ok(buffer)
+}
@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::{
+    log::log_info,
+    dump::Direction
+};
+
+
+//counted over the whole run of the proxy, printed when it shuts down
+static CONNECTIONS: AtomicU64 = AtomicU64::new(0);
+static MESSAGES: AtomicU64 = AtomicU64::new(0);
+
+static CLIENT_BYTES: AtomicU64 = AtomicU64::new(0);
+static SERVER_BYTES: AtomicU64 = AtomicU64::new(0);
+
+//lengths of the colored messages before and after coloring
+static ORIGINAL_BYTES: AtomicU64 = AtomicU64::new(0);
+static COLORED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+//how long coloring took, only timed with profiling on
+static PROFILE: AtomicBool = AtomicBool::new(false);
+static COLORING_MICROS: AtomicU64 = AtomicU64::new(0);
+static TIMED_MESSAGES: AtomicU64 = AtomicU64::new(0);
+
+pub fn set_profile(profile: bool)
+{
+    PROFILE.store(profile, Ordering::Relaxed);
+}
+
+pub fn profiling() -> bool
+{
+    PROFILE.load(Ordering::Relaxed)
+}
+
+pub fn record_connection()
+{
+    CONNECTIONS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn record_bytes(direction: Direction, amount: usize)
+{
+    let counter = match direction
+    {
+        Direction::Client => &CLIENT_BYTES,
+        Direction::Server => &SERVER_BYTES
+    };
+
+    counter.fetch_add(amount as u64, Ordering::Relaxed);
+}
+
+pub fn record_message(original: usize, colored: usize)
+{
+    MESSAGES.fetch_add(1, Ordering::Relaxed);
+
+    ORIGINAL_BYTES.fetch_add(original as u64, Ordering::Relaxed);
+    COLORED_BYTES.fetch_add(colored as u64, Ordering::Relaxed);
+}
+
+//returns the average time in microseconds so far
+pub fn record_coloring(time: Duration) -> u64
+{
+    let total = COLORING_MICROS.fetch_add(time.as_micros() as u64, Ordering::Relaxed)
+        + time.as_micros() as u64;
+    let amount = TIMED_MESSAGES.fetch_add(1, Ordering::Relaxed) + 1;
+
+    total / amount
+}
+
+pub fn print_stats()
+{
+    let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+
+    log_info!("connections handled: {}", load(&CONNECTIONS));
+    log_info!("chat messages colored: {}", load(&MESSAGES));
+    log_info!("bytes from the client: {}", load(&CLIENT_BYTES));
+    log_info!("bytes from the server: {}", load(&SERVER_BYTES));
+
+    let original = load(&ORIGINAL_BYTES);
+    if original>0
+    {
+        let ratio = load(&COLORED_BYTES) as f64 / original as f64;
+
+        log_info!("colored messages are {ratio:.2} times as long on average");
+    }
+
+    if let Some(average) = load(&COLORING_MICROS).checked_div(load(&TIMED_MESSAGES))
+    {
+        log_info!("coloring a message took {average}us on average");
+    }
+}
@@ -0,0 +1,266 @@
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use std::io::{Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+
+use terraria_cool_colorer::{ProxyConfig, ColorerSettings, Color, MAX_MESSAGE_LENGTH, serve_proxy};
+
+
+fn config(upstream: SocketAddr) -> ProxyConfig
+{
+    ProxyConfig{
+        connect_addresses: vec![upstream.to_string()],
+        listen_address: upstream.ip(),
+        allow_remote_clients: false,
+        port: 0,
+        once: true,
+        timeout: None,
+        color_incoming: false,
+        color_system: false,
+        color_combat_text: false,
+        echo: false,
+        max_length: MAX_MESSAGE_LENGTH,
+        dump: None,
+        socks5: None,
+        max_connections: None,
+        no_color: false,
+        nodelay: true,
+        profile: false,
+        banner: None,
+        colors: vec![Color::new(255, 0, 0)],
+        colorer: ColorerSettings{shift: false, ..Default::default()}
+        }
+}
+
+//a chat packet from the client, short enough that every length fits in one byte
+fn say(message: &str) -> Vec<u8>
+{
+    let mut body = vec![0x52, 0x01, 0x00, 3];
+    body.extend(b"Say");
+
+    body.push(message.len() as u8);
+    body.extend(message.bytes());
+
+    let mut packet = ((body.len() + 2) as u16).to_le_bytes().to_vec();
+    packet.extend(body);
+
+    packet
+}
+
+type Handles = (SocketAddr, JoinHandle<Vec<u8>>, JoinHandle<Result<(), String>>);
+
+//a proxy in front of a fake server, gives the address to connect to
+fn spawn_proxy(
+    config: fn(SocketAddr) -> ProxyConfig,
+    server: impl FnOnce(TcpStream) -> Vec<u8> + Send + 'static
+    ) -> Handles
+{
+    let upstream = TcpListener::bind("127.0.0.1:0").unwrap();
+    let upstream_address = upstream.local_addr().unwrap();
+
+    let server = thread::spawn(move ||
+    {
+        let (stream, _) = upstream.accept().unwrap();
+
+        server(stream)
+    });
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy_address = listener.local_addr().unwrap();
+
+    let proxy = thread::spawn(move || serve_proxy(listener, &config(upstream_address)));
+
+    (proxy_address, server, proxy)
+}
+
+//sends the bytes through a proxy and returns whatever the server got
+fn proxied(sent: &[u8], server: impl FnOnce(TcpStream) -> Vec<u8> + Send + 'static) -> Vec<u8>
+{
+    let (proxy_address, server, proxy) = spawn_proxy(config, server);
+
+    let mut client = TcpStream::connect(proxy_address).unwrap();
+    client.write_all(sent).unwrap();
+    client.shutdown(Shutdown::Write).unwrap();
+
+    //the server closing its side ends the session
+    let mut ignored = Vec::new();
+    let _ = client.read_to_end(&mut ignored);
+
+    let received = server.join().unwrap();
+
+    proxy.join().unwrap().unwrap();
+
+    received
+}
+
+fn read_all(mut stream: TcpStream) -> Vec<u8>
+{
+    let mut received = Vec::new();
+    stream.read_to_end(&mut received).unwrap();
+
+    received
+}
+
+#[test]
+fn chat_arrives_colored()
+{
+    let received = proxied(&say("hello"), read_all);
+
+    assert_eq!(received, say("[c/ff0000:hello]"));
+}
+
+#[test]
+fn slow_server_loses_nothing()
+{
+    //big enough to fill the socket buffers so writes to the server have to wait
+    let mut tile_section = vec![0x0a; 60000];
+    tile_section[..2].copy_from_slice(&60000_u16.to_le_bytes());
+
+    let mut sent = Vec::new();
+    let mut expected = Vec::new();
+    for _ in 0..30
+    {
+        sent.extend(&tile_section);
+        sent.extend(say("hi"));
+
+        expected.extend(&tile_section);
+        expected.extend(say("[c/ff0000:hi]"));
+    }
+
+    let received = proxied(&sent, |mut stream|
+    {
+        let mut received = Vec::new();
+
+        let mut buffer = [0; 4096];
+        loop
+        {
+            let amount = stream.read(&mut buffer).unwrap();
+            if amount==0
+            {
+                return received;
+            }
+
+            received.extend(&buffer[..amount]);
+            thread::sleep(Duration::from_millis(1));
+        }
+    });
+
+    assert_eq!(received.len(), expected.len());
+    assert!(received==expected);
+}
+
+#[test]
+fn server_closing_right_away_ends_the_session()
+{
+    let (proxy_address, server, proxy) = spawn_proxy(config, |stream|
+    {
+        drop(stream);
+
+        Vec::new()
+    });
+
+    let mut client = TcpStream::connect(proxy_address).unwrap();
+    client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    //the proxy closes the client too instead of waiting on it
+    let mut received = Vec::new();
+    client.read_to_end(&mut received).unwrap();
+
+    assert!(received.is_empty());
+
+    server.join().unwrap();
+    proxy.join().unwrap().unwrap();
+}
+
+#[test]
+fn packet_split_right_after_the_banner()
+{
+    //any packet that isnt chat
+    let next = [7, 0, 0x0d, 1, 2, 3, 4];
+
+    let with_banner = |address| ProxyConfig{banner: Some("welcome".to_string()), ..config(address)};
+
+    let (proxy_address, server, proxy) = spawn_proxy(with_banner, move |mut stream|
+    {
+        stream.set_nodelay(true).unwrap();
+
+        //the client is in the world and the next packet starts in the same read
+        stream.write_all(&[3, 0, 0x81, next[0]]).unwrap();
+        thread::sleep(Duration::from_millis(200));
+
+        stream.write_all(&next[1..]).unwrap();
+
+        Vec::new()
+    });
+
+    let mut client = TcpStream::connect(proxy_address).unwrap();
+    client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let mut received = Vec::new();
+    client.read_to_end(&mut received).unwrap();
+
+    assert_eq!(received[..3], [3, 0, 0x81]);
+
+    let banner_length = u16::from_le_bytes([received[3], received[4]]) as usize;
+    assert_eq!(received[3 + banner_length..], next);
+
+    server.join().unwrap();
+    proxy.join().unwrap().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn accept_error_lets_sessions_finish()
+{
+    use std::sync::mpsc;
+    use std::os::fd::{FromRawFd, IntoRawFd};
+
+    let next = [7, 0, 0x0d, 1, 2, 3, 4];
+
+    let upstream = TcpListener::bind("127.0.0.1:0").unwrap();
+    let upstream_address = upstream.local_addr().unwrap();
+
+    let (connected, wait_connected) = mpsc::channel();
+    let server = thread::spawn(move ||
+    {
+        let (mut stream, _) = upstream.accept().unwrap();
+        connected.send(()).unwrap();
+
+        //still going when accepting fails
+        thread::sleep(Duration::from_millis(500));
+        stream.write_all(&next).unwrap();
+    });
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy_address = listener.local_addr().unwrap();
+    let listening = listener.try_clone().unwrap();
+
+    let proxy = thread::spawn(move ||
+    {
+        serve_proxy(listener, &ProxyConfig{once: false, ..config(upstream_address)})
+    });
+
+    let mut client = TcpStream::connect(proxy_address).unwrap();
+    client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    wait_connected.recv().unwrap();
+
+    let failed = Instant::now();
+
+    //a listening socket thats shut down fails every accept after it
+    let listening = unsafe{ TcpStream::from_raw_fd(listening.into_raw_fd()) };
+    listening.shutdown(Shutdown::Read).unwrap();
+
+    let error = proxy.join().unwrap().unwrap_err();
+    assert!(error.starts_with("could not establish connection"), "{error}");
+
+    //the session got to finish before the error came back
+    assert!(failed.elapsed()>=Duration::from_millis(300));
+
+    let mut received = Vec::new();
+    client.read_to_end(&mut received).unwrap();
+
+    assert_eq!(received, next);
+
+    server.join().unwrap();
+}